@@ -0,0 +1,31 @@
+//! Clears an OpenGL window.
+//!
+//! Run with `LIBGL_ALWAYS_SOFTWARE=1` to use Mesa's software rasterizer.
+
+extern crate glow;
+extern crate rg_winit_window;
+extern crate window;
+
+use glow::HasContext;
+use window::{OpenGLWindow, Window, WindowSettings};
+
+use rg_winit_window::WinitWindow;
+
+fn main() {
+    let mut window = WinitWindow::new(&WindowSettings::new("Winit Window", (640, 480)));
+    assert!(window.is_current());
+    {
+        let gl = window.gl().expect("an OpenGL context");
+        println!("Created OpenGL context: {}", unsafe { gl.get_parameter_string(glow::VERSION) });
+    }
+    while !window.should_close() {
+        window.wait_event();
+        if let Some(gl) = window.gl() {
+            unsafe {
+                gl.clear_color(0.2, 0.3, 0.4, 1.0);
+                gl.clear(glow::COLOR_BUFFER_BIT);
+            }
+        }
+        window.swap_buffers();
+    }
+}
@@ -1,16 +1,20 @@
 extern crate vulkano;
-extern crate winit_window;
+extern crate rg_winit_window;
 extern crate window;
 
 use vulkano::instance::{Instance};
-use window::{WindowSettings};
+use window::{Window, WindowSettings};
 
-use winit_window::{WinitWindow};
+use rg_winit_window::{WinitWindow};
 
 fn main() {
-    let instance = Instance::new(None, &winit_window::required_extensions(), None).unwrap();
-    let _ = WinitWindow::new_vulkano(
+    let instance = Instance::new(None, &rg_winit_window::required_extensions(), None).unwrap();
+    let mut window = WinitWindow::new_vulkano(
         instance,
         &WindowSettings::new("Winit Window", (640, 480)),
     );
+    println!("Created Vulkan surface for {:?}", window.surface().window().id());
+    while !window.should_close() {
+        window.wait_event();
+    }
 }
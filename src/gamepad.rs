@@ -0,0 +1,166 @@
+//! Gamepad and joystick input through gilrs, enabled by the `gamepad` feature.
+//!
+//! Buttons and axes are numbered like SDL game controllers,
+//! such that bindings carry over from the sdl2 back-end.
+//! Controller ids are the gilrs gamepad ids,
+//! which are kept when a gamepad is reconnected.
+
+use std::collections::HashMap;
+
+use gilrs::{Axis, Button as GilrsButton, EventType, Gilrs};
+use input::{
+    Button,
+    ButtonArgs,
+    ButtonState,
+    ControllerAxisArgs,
+    ControllerButton,
+    ControllerHat,
+    HatState,
+    Input,
+    Motion,
+};
+
+const HAT_UP: u8 = 1;
+const HAT_DOWN: u8 = 2;
+const HAT_LEFT: u8 = 4;
+const HAT_RIGHT: u8 = 8;
+
+/// Polls the connected gamepads.
+pub struct Gamepads {
+    gilrs: Gilrs,
+    // The pressed directions of the D-pad, by controller id.
+    hats: HashMap<usize, u8>,
+}
+
+impl Gamepads {
+    /// Connects to the gamepad subsystem.
+    ///
+    /// Returns `None` when gamepads are not supported on this platform.
+    pub fn new() -> Option<Gamepads> {
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Gamepads { gilrs: gilrs, hats: HashMap::new() }),
+            Err(err) => {
+                warn!("gamepads are not available: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Returns the next gamepad input, or `None` when there are no pending events.
+    pub fn poll(&mut self) -> Option<Input> {
+        while let Some(ev) = self.gilrs.next_event() {
+            let id: usize = ev.id.into();
+            trace!("gamepad event: id={} event={:?}", id, ev.event);
+            if let Some(input) = self.translate(id, ev.event) {
+                return Some(input);
+            }
+        }
+        None
+    }
+
+    fn translate(&mut self, id: usize, event: EventType) -> Option<Input> {
+        match event {
+            EventType::ButtonPressed(button, _) => self.button(id, button, ButtonState::Press),
+            EventType::ButtonReleased(button, _) => self.button(id, button, ButtonState::Release),
+            // Analog triggers are reported as axes, like SDL.
+            EventType::ButtonChanged(GilrsButton::LeftTrigger2, value, _) => Some(axis(id, 4, value as f64)),
+            EventType::ButtonChanged(GilrsButton::RightTrigger2, value, _) => Some(axis(id, 5, value as f64)),
+            EventType::AxisChanged(a, value, _) => {
+                // SDL reports the vertical stick axes pointing down.
+                match a {
+                    Axis::LeftStickX => Some(axis(id, 0, value as f64)),
+                    Axis::LeftStickY => Some(axis(id, 1, -value as f64)),
+                    Axis::RightStickX => Some(axis(id, 2, value as f64)),
+                    Axis::RightStickY => Some(axis(id, 3, -value as f64)),
+                    Axis::LeftZ => Some(axis(id, 4, value as f64)),
+                    Axis::RightZ => Some(axis(id, 5, value as f64)),
+                    // The D-pad axes are reported as buttons by the default filters.
+                    Axis::DPadX | Axis::DPadY | Axis::Unknown => None,
+                }
+            }
+            EventType::Disconnected => {
+                self.hats.remove(&id);
+                None
+            }
+            EventType::ButtonRepeated(..) |
+            EventType::ButtonChanged(..) |
+            EventType::Connected |
+            EventType::Dropped => None,
+        }
+    }
+
+    fn button(&mut self, id: usize, button: GilrsButton, state: ButtonState) -> Option<Input> {
+        let direction = match button {
+            GilrsButton::DPadUp => HAT_UP,
+            GilrsButton::DPadDown => HAT_DOWN,
+            GilrsButton::DPadLeft => HAT_LEFT,
+            GilrsButton::DPadRight => HAT_RIGHT,
+            button => {
+                let button = map_button(button)?;
+                return Some(Input::Button(ButtonArgs {
+                    state: state,
+                    button: Button::Controller(ControllerButton::new(id as _, button)),
+                    scancode: None,
+                }));
+            }
+        };
+
+        // The D-pad is reported as a single hat.
+        let hat = self.hats.entry(id).or_insert(0);
+        match state {
+            ButtonState::Press => *hat |= direction,
+            ButtonState::Release => *hat &= !direction,
+        }
+        Some(Input::Button(ButtonArgs {
+            state: state,
+            button: Button::Hat(ControllerHat::new(id as _, 0, hat_state(*hat))),
+            scancode: None,
+        }))
+    }
+}
+
+fn axis(id: usize, axis: u8, position: f64) -> Input {
+    Input::Move(Motion::ControllerAxis(ControllerAxisArgs::new(id as _, axis, position)))
+}
+
+fn hat_state(hat: u8) -> HatState {
+    match (hat & (HAT_UP | HAT_DOWN), hat & (HAT_LEFT | HAT_RIGHT)) {
+        (HAT_UP, HAT_LEFT) => HatState::LeftUp,
+        (HAT_UP, HAT_RIGHT) => HatState::RightUp,
+        (HAT_DOWN, HAT_LEFT) => HatState::LeftDown,
+        (HAT_DOWN, HAT_RIGHT) => HatState::RightDown,
+        (HAT_UP, _) => HatState::Up,
+        (HAT_DOWN, _) => HatState::Down,
+        (_, HAT_LEFT) => HatState::Left,
+        (_, HAT_RIGHT) => HatState::Right,
+        _ => HatState::Centered,
+    }
+}
+
+/// Maps a gilrs button to the index of the SDL game controller button.
+///
+/// Buttons without an SDL equivalent are numbered after the SDL buttons.
+fn map_button(button: GilrsButton) -> Option<u8> {
+    Some(match button {
+        GilrsButton::South => 0,
+        GilrsButton::East => 1,
+        GilrsButton::West => 2,
+        GilrsButton::North => 3,
+        GilrsButton::Select => 4,
+        GilrsButton::Mode => 5,
+        GilrsButton::Start => 6,
+        GilrsButton::LeftThumb => 7,
+        GilrsButton::RightThumb => 8,
+        GilrsButton::LeftTrigger => 9,
+        GilrsButton::RightTrigger => 10,
+        GilrsButton::DPadUp => 11,
+        GilrsButton::DPadDown => 12,
+        GilrsButton::DPadLeft => 13,
+        GilrsButton::DPadRight => 14,
+        GilrsButton::C => 15,
+        GilrsButton::Z => 16,
+        GilrsButton::LeftTrigger2 => 17,
+        GilrsButton::RightTrigger2 => 18,
+        GilrsButton::Unknown => return None,
+    })
+}
@@ -0,0 +1,448 @@
+//! Recognizes gestures from touch input.
+//!
+//! Gestures are emitted as custom events with id `GESTURE_EVENT_ID`,
+//! carrying a `Gesture` as payload.
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::time::{Duration, Instant};
+
+use input::{EventId, Touch, TouchArgs};
+
+/// The id of custom events carrying a `Gesture`.
+pub const GESTURE_EVENT_ID: EventId = EventId("winit/gesture");
+
+/// A gesture recognized from touch input.
+///
+/// Positions and distances are in logical pixels, like touch positions.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Gesture {
+    /// A finger touched and was lifted at a position.
+    Tap([f64; 2]),
+    /// A second tap near the previous one.
+    /// Emitted after the `Tap` of the second tap.
+    DoubleTap([f64; 2]),
+    /// A finger was held at a position.
+    /// The finger does not tap when lifted.
+    LongPress([f64; 2]),
+    /// Two fingers moved the center between them by a relative amount.
+    Pan([f64; 2]),
+    /// Two fingers changed the distance between them by a factor.
+    Pinch(f64),
+    /// Two fingers rotated around each other by an angle in radians,
+    /// clockwise in window coordinates.
+    Rotate(f64),
+}
+
+/// Thresholds for recognizing gestures.
+#[derive(Copy, Clone, Debug)]
+pub struct GestureSettings {
+    /// How far a finger can move while still tapping or long pressing.
+    pub tap_distance: f64,
+    /// How long a finger can touch while still tapping.
+    pub tap_time: Duration,
+    /// The maximum time between two taps of a double tap.
+    pub double_tap_time: Duration,
+    /// The maximum distance between two taps of a double tap.
+    pub double_tap_distance: f64,
+    /// How long a finger must be held for a long press.
+    pub long_press_time: Duration,
+    /// How far the center between two fingers must move before panning.
+    pub pan_distance: f64,
+    /// The factor by which the distance between two fingers must change before pinching.
+    /// Pinching starts when the distance grows by this factor or shrinks by its inverse.
+    pub pinch_scale: f64,
+    /// The angle in radians two fingers must rotate before rotating.
+    pub rotate_angle: f64,
+}
+
+impl Default for GestureSettings {
+    fn default() -> GestureSettings {
+        GestureSettings {
+            tap_distance: 10.0,
+            tap_time: Duration::from_millis(300),
+            double_tap_time: Duration::from_millis(300),
+            double_tap_distance: 40.0,
+            long_press_time: Duration::from_millis(500),
+            pan_distance: 10.0,
+            pinch_scale: 1.1,
+            rotate_angle: PI / 18.0,
+        }
+    }
+}
+
+// A finger touching the window.
+struct Finger {
+    start_pos: [f64; 2],
+    start_time: Instant,
+    pos: [f64; 2],
+    // Set when the finger moves too far to tap.
+    moved: bool,
+    long_pressed: bool,
+}
+
+// The motion of two fingers since they touched.
+// Each gesture accumulates motion until its threshold is reached,
+// and then emits every motion until the fingers change.
+struct TwoFingerMotion {
+    pan: [f64; 2],
+    scale: f64,
+    rotation: f64,
+    panning: bool,
+    pinching: bool,
+    rotating: bool,
+}
+
+impl TwoFingerMotion {
+    fn new() -> TwoFingerMotion {
+        TwoFingerMotion {
+            pan: [0.0, 0.0],
+            scale: 1.0,
+            rotation: 0.0,
+            panning: false,
+            pinching: false,
+            rotating: false,
+        }
+    }
+}
+
+/// Tracks the fingers touching the window and queues the recognized gestures.
+pub struct GestureRecognizer {
+    pub settings: GestureSettings,
+    // The fingers by device and touch id, in the order they touched.
+    fingers: Vec<((i64, i64), Finger)>,
+    // The number of fingers in the current gesture,
+    // reset when all fingers are lifted.
+    max_fingers: usize,
+    // The motion of two fingers, reset when a finger touches or is lifted.
+    motion: TwoFingerMotion,
+    // The position and time of the last tap, for double taps.
+    last_tap: Option<([f64; 2], Instant)>,
+    gestures: VecDeque<Gesture>,
+}
+
+impl GestureRecognizer {
+    pub fn new(settings: GestureSettings) -> GestureRecognizer {
+        GestureRecognizer {
+            settings: settings,
+            fingers: vec![],
+            max_fingers: 0,
+            motion: TwoFingerMotion::new(),
+            last_tap: None,
+            gestures: VecDeque::new(),
+        }
+    }
+
+    /// Updates the fingers with a touch input.
+    pub fn touch(&mut self, args: &TouchArgs, now: Instant) {
+        let key = (args.device, args.id);
+        let pos = args.position();
+        match args.touch {
+            Touch::Start => {
+                self.fingers.retain(|f| f.0 != key);
+                self.fingers.push((key, Finger {
+                    start_pos: pos,
+                    start_time: now,
+                    pos: pos,
+                    moved: false,
+                    long_pressed: false,
+                }));
+                self.max_fingers = self.max_fingers.max(self.fingers.len());
+                self.motion = TwoFingerMotion::new();
+            }
+            Touch::Move => {
+                let before = self.two_fingers();
+                let tap_distance = self.settings.tap_distance;
+                if let Some(f) = self.fingers.iter_mut().find(|f| f.0 == key) {
+                    let finger = &mut f.1;
+                    finger.pos = pos;
+                    if distance(finger.start_pos, pos) > tap_distance {
+                        finger.moved = true;
+                    }
+                }
+                if let (Some(a), Some(b)) = (before, self.two_fingers()) {
+                    self.two_finger_motion(a, b);
+                }
+            }
+            Touch::End | Touch::Cancel => {
+                let index = match self.fingers.iter().position(|f| f.0 == key) {
+                    Some(index) => index,
+                    None => return,
+                };
+                let (_, finger) = self.fingers.remove(index);
+                self.motion = TwoFingerMotion::new();
+                // The finger can move without a move input before it is lifted.
+                let moved = finger.moved || distance(finger.start_pos, pos) > self.settings.tap_distance;
+                if args.touch == Touch::End && self.max_fingers == 1 && !moved &&
+                   !finger.long_pressed && now - finger.start_time <= self.settings.tap_time {
+                    self.tap(pos, now);
+                }
+                if self.fingers.is_empty() {
+                    self.max_fingers = 0;
+                }
+            }
+        }
+    }
+
+    /// Pops the next recognized gesture.
+    pub fn pop(&mut self, now: Instant) -> Option<Gesture> {
+        if self.max_fingers == 1 {
+            let long_press_time = self.settings.long_press_time;
+            if let Some(f) = self.fingers.first_mut() {
+                let finger = &mut f.1;
+                if !finger.moved && !finger.long_pressed && now - finger.start_time >= long_press_time {
+                    finger.long_pressed = true;
+                    self.gestures.push_back(Gesture::LongPress(finger.pos));
+                }
+            }
+        }
+        self.gestures.pop_front()
+    }
+
+    /// Returns when a long press is recognized, unless the finger moves or is lifted.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.max_fingers != 1 {
+            return None;
+        }
+        match self.fingers.first() {
+            Some(f) if !f.1.moved && !f.1.long_pressed =>
+                Some(f.1.start_time + self.settings.long_press_time),
+            _ => None,
+        }
+    }
+
+    fn tap(&mut self, pos: [f64; 2], now: Instant) {
+        self.gestures.push_back(Gesture::Tap(pos));
+        match self.last_tap {
+            Some((last_pos, last_time)) if now - last_time <= self.settings.double_tap_time &&
+                distance(last_pos, pos) <= self.settings.double_tap_distance => {
+                self.gestures.push_back(Gesture::DoubleTap(pos));
+                // A third tap starts a new double tap.
+                self.last_tap = None;
+            }
+            _ => self.last_tap = Some((pos, now)),
+        }
+    }
+
+    // Returns the positions of the first two fingers, when exactly two fingers touch.
+    fn two_fingers(&self) -> Option<([f64; 2], [f64; 2])> {
+        if self.fingers.len() == 2 {
+            Some((self.fingers[0].1.pos, self.fingers[1].1.pos))
+        } else {
+            None
+        }
+    }
+
+    fn two_finger_motion(&mut self, before: ([f64; 2], [f64; 2]), after: ([f64; 2], [f64; 2])) {
+        let center = |(a, b): ([f64; 2], [f64; 2])| [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+        let angle = |(a, b): ([f64; 2], [f64; 2])| (b[1] - a[1]).atan2(b[0] - a[0]);
+
+        let settings = self.settings;
+        let motion = &mut self.motion;
+
+        let (c0, c1) = (center(before), center(after));
+        let pan = [c1[0] - c0[0], c1[1] - c0[1]];
+        if pan != [0.0, 0.0] {
+            motion.pan = [motion.pan[0] + pan[0], motion.pan[1] + pan[1]];
+            if motion.panning || distance([0.0, 0.0], motion.pan) >= settings.pan_distance {
+                // The first pan includes the motion below the threshold.
+                self.gestures.push_back(Gesture::Pan(if motion.panning { pan } else { motion.pan }));
+                motion.panning = true;
+            }
+        }
+
+        let (d0, d1) = (distance(before.0, before.1), distance(after.0, after.1));
+        if d0 > 0.0 && d1 != d0 {
+            let scale = d1 / d0;
+            motion.scale *= scale;
+            if motion.pinching || motion.scale >= settings.pinch_scale ||
+               motion.scale <= 1.0 / settings.pinch_scale {
+                self.gestures.push_back(Gesture::Pinch(if motion.pinching { scale } else { motion.scale }));
+                motion.pinching = true;
+            }
+        }
+
+        let mut rotation = angle(after) - angle(before);
+        if rotation > PI {
+            rotation -= 2.0 * PI;
+        } else if rotation < -PI {
+            rotation += 2.0 * PI;
+        }
+        if d0 > 0.0 && d1 > 0.0 && rotation != 0.0 {
+            motion.rotation += rotation;
+            if motion.rotating || motion.rotation.abs() >= settings.rotate_angle {
+                self.gestures.push_back(Gesture::Rotate(if motion.rotating { rotation } else { motion.rotation }));
+                motion.rotating = true;
+            }
+        }
+    }
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (b[0] - a[0]).hypot(b[1] - a[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(recognizer: &mut GestureRecognizer, id: i64, pos: [f64; 2], touch: Touch, now: Instant) {
+        recognizer.touch(&TouchArgs::new(0, id, pos, 1.0, touch), now);
+    }
+
+    fn gestures(recognizer: &mut GestureRecognizer, now: Instant) -> Vec<Gesture> {
+        let mut gestures = vec![];
+        while let Some(gesture) = recognizer.pop(now) {
+            gestures.push(gesture);
+        }
+        gestures
+    }
+
+    #[test]
+    fn tap() {
+        let mut recognizer = GestureRecognizer::new(GestureSettings::default());
+        let now = Instant::now();
+        let later = now + Duration::from_millis(100);
+        touch(&mut recognizer, 0, [5.0, 5.0], Touch::Start, now);
+        touch(&mut recognizer, 0, [6.0, 5.0], Touch::End, later);
+        assert_eq!(gestures(&mut recognizer, later), vec![Gesture::Tap([6.0, 5.0])]);
+    }
+
+    #[test]
+    fn moved_finger_does_not_tap() {
+        let mut recognizer = GestureRecognizer::new(GestureSettings::default());
+        let now = Instant::now();
+        touch(&mut recognizer, 0, [0.0, 0.0], Touch::Start, now);
+        touch(&mut recognizer, 0, [50.0, 0.0], Touch::Move, now);
+        touch(&mut recognizer, 0, [50.0, 0.0], Touch::End, now);
+        assert_eq!(gestures(&mut recognizer, now), vec![]);
+    }
+
+    #[test]
+    fn lifted_far_away_does_not_tap() {
+        let mut recognizer = GestureRecognizer::new(GestureSettings::default());
+        let now = Instant::now();
+        touch(&mut recognizer, 0, [0.0, 0.0], Touch::Start, now);
+        touch(&mut recognizer, 0, [50.0, 0.0], Touch::End, now);
+        assert_eq!(gestures(&mut recognizer, now), vec![]);
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut recognizer = GestureRecognizer::new(GestureSettings::default());
+        let mut now = Instant::now();
+        for _ in 0..2 {
+            touch(&mut recognizer, 0, [5.0, 5.0], Touch::Start, now);
+            now += Duration::from_millis(50);
+            touch(&mut recognizer, 0, [5.0, 5.0], Touch::End, now);
+            now += Duration::from_millis(50);
+        }
+        assert_eq!(gestures(&mut recognizer, now), vec![
+            Gesture::Tap([5.0, 5.0]),
+            Gesture::Tap([5.0, 5.0]),
+            Gesture::DoubleTap([5.0, 5.0]),
+        ]);
+    }
+
+    #[test]
+    fn long_press() {
+        let settings = GestureSettings::default();
+        let mut recognizer = GestureRecognizer::new(settings);
+        let now = Instant::now();
+        touch(&mut recognizer, 0, [5.0, 5.0], Touch::Start, now);
+        assert_eq!(recognizer.deadline(), Some(now + settings.long_press_time));
+        assert_eq!(gestures(&mut recognizer, now), vec![]);
+        let later = now + settings.long_press_time;
+        assert_eq!(gestures(&mut recognizer, later), vec![Gesture::LongPress([5.0, 5.0])]);
+        assert_eq!(recognizer.deadline(), None);
+        // A long press does not tap when lifted.
+        touch(&mut recognizer, 0, [5.0, 5.0], Touch::End, later);
+        assert_eq!(gestures(&mut recognizer, later), vec![]);
+    }
+
+    #[test]
+    fn pan() {
+        let mut recognizer = GestureRecognizer::new(GestureSettings::default());
+        let now = Instant::now();
+        touch(&mut recognizer, 0, [0.0, 0.0], Touch::Start, now);
+        touch(&mut recognizer, 1, [10.0, 0.0], Touch::Start, now);
+        touch(&mut recognizer, 0, [0.0, 10.0], Touch::Move, now);
+        touch(&mut recognizer, 1, [10.0, 10.0], Touch::Move, now);
+
+        let mut pan = [0.0, 0.0];
+        let mut scale = 1.0;
+        let mut rotation = 0.0;
+        for gesture in gestures(&mut recognizer, now) {
+            match gesture {
+                Gesture::Pan(delta) => pan = [pan[0] + delta[0], pan[1] + delta[1]],
+                Gesture::Pinch(factor) => scale *= factor,
+                Gesture::Rotate(angle) => rotation += angle,
+                gesture => panic!("unexpected gesture {:?}", gesture),
+            }
+        }
+        assert_eq!(pan, [0.0, 10.0]);
+        assert!((scale - 1.0).abs() < 1e-9);
+        assert!(rotation.abs() < 1e-9);
+    }
+
+    #[test]
+    fn pinch() {
+        let mut recognizer = GestureRecognizer::new(GestureSettings::default());
+        let now = Instant::now();
+        touch(&mut recognizer, 0, [0.0, 0.0], Touch::Start, now);
+        touch(&mut recognizer, 1, [10.0, 0.0], Touch::Start, now);
+        touch(&mut recognizer, 1, [20.0, 0.0], Touch::Move, now);
+        // The center moves less than `pan_distance`.
+        assert_eq!(gestures(&mut recognizer, now), vec![Gesture::Pinch(2.0)]);
+        // Two fingers do not tap.
+        touch(&mut recognizer, 0, [0.0, 0.0], Touch::End, now);
+        touch(&mut recognizer, 1, [20.0, 0.0], Touch::End, now);
+        assert_eq!(gestures(&mut recognizer, now), vec![]);
+    }
+
+    #[test]
+    fn rotate_clockwise() {
+        let mut recognizer = GestureRecognizer::new(GestureSettings::default());
+        let now = Instant::now();
+        touch(&mut recognizer, 0, [0.0, 0.0], Touch::Start, now);
+        touch(&mut recognizer, 1, [10.0, 0.0], Touch::Start, now);
+        // Down is clockwise in window coordinates.
+        touch(&mut recognizer, 1, [0.0, 10.0], Touch::Move, now);
+        let rotation = gestures(&mut recognizer, now).into_iter().filter_map(|gesture| match gesture {
+            Gesture::Rotate(angle) => Some(angle),
+            _ => None,
+        }).sum::<f64>();
+        assert!((rotation - PI / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn thresholds() {
+        let settings = GestureSettings::default();
+        let mut recognizer = GestureRecognizer::new(settings);
+        let now = Instant::now();
+        touch(&mut recognizer, 0, [0.0, 0.0], Touch::Start, now);
+        touch(&mut recognizer, 1, [100.0, 0.0], Touch::Start, now);
+        // Small motions of both fingers are below every threshold.
+        touch(&mut recognizer, 0, [2.0, 1.0], Touch::Move, now);
+        touch(&mut recognizer, 1, [102.0, 0.0], Touch::Move, now);
+        assert_eq!(gestures(&mut recognizer, now), vec![]);
+
+        // The motion accumulates until the fingers pan.
+        touch(&mut recognizer, 0, [10.0, 0.0], Touch::Move, now);
+        touch(&mut recognizer, 1, [110.0, 0.0], Touch::Move, now);
+        assert_eq!(gestures(&mut recognizer, now), vec![Gesture::Pan([10.0, 0.0])]);
+        // Panning continues below the threshold.
+        touch(&mut recognizer, 0, [11.0, 0.0], Touch::Move, now);
+        touch(&mut recognizer, 1, [111.0, 0.0], Touch::Move, now);
+        assert_eq!(gestures(&mut recognizer, now), vec![
+            Gesture::Pan([0.5, 0.0]),
+            Gesture::Pan([0.5, 0.0]),
+        ]);
+
+        // Lifting a finger resets the thresholds.
+        touch(&mut recognizer, 1, [111.0, 0.0], Touch::End, now);
+        touch(&mut recognizer, 1, [111.0, 0.0], Touch::Start, now);
+        touch(&mut recognizer, 0, [12.0, 0.0], Touch::Move, now);
+        assert_eq!(gestures(&mut recognizer, now), vec![]);
+    }
+}
@@ -3,14 +3,32 @@ extern crate winit;
 extern crate vulkano;
 #[cfg(feature="use-vulkano")]
 extern crate vulkano_win;
+#[cfg(feature="use-opengl")]
+extern crate glutin;
+#[cfg(feature="use-opengl")]
+extern crate glow;
+#[cfg(all(feature="use-software", unix))]
+extern crate libc;
+#[cfg(all(feature="use-software", unix))]
+extern crate tempfile;
+#[cfg(all(feature="use-software", unix))]
+extern crate wayland_client;
+#[cfg(all(any(feature="use-software", feature="use-opengl"), unix))]
+extern crate x11_dl;
+#[cfg(all(feature="use-software", windows))]
+extern crate winapi;
+#[cfg(feature="gamepad")]
+extern crate gilrs;
+#[macro_use]
+extern crate log;
 extern crate input;
 extern crate window;
+extern crate raw_window_handle;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::collections::VecDeque;
 use std::error::Error;
-
-
+use std::fmt;
 #[cfg(feature="use-vulkano")]
 use std::sync::Arc;
 
@@ -21,30 +39,25 @@ use vulkano::{
 };
 
 use winit::{
-    event_loop::EventLoop,
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     window::Window as OriginalWinitWindow,
     window::WindowBuilder,
     event::Event as WinitEvent,
-    event::WindowEvent,
-    event::ElementState,
     event::MouseButton as WinitMouseButton,
-    event::KeyboardInput,
-    event::MouseScrollDelta,
-    dpi::{LogicalPosition, LogicalSize},
+    dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize},
 };
 use input::{
     keyboard,
     ButtonArgs,
     ButtonState,
-    CloseArgs,
     Event,
+    EventId,
     MouseButton,
     Button,
     Input,
-    FileDrag,
-    ResizeArgs,
     Key,
 };
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use window::{
     BuildFromWindowSettings,
     OpenGLWindow,
@@ -60,12 +73,50 @@ use window::{
 #[cfg(feature="use-vulkano")]
 pub use vulkano_win::required_extensions;
 
-pub struct WinitWindow {
+#[cfg(feature="use-opengl")]
+mod opengl;
+#[cfg(feature="use-vulkano")]
+mod surface;
+#[cfg(feature="use-software")]
+mod software;
+#[cfg(feature="gamepad")]
+mod gamepad;
+mod gesture;
+mod state;
+
+#[cfg(feature="use-software")]
+pub use software::{Framebuffer, FramebufferError};
+pub use gesture::{Gesture, GestureSettings, GESTURE_EVENT_ID};
+
+/// The id of custom events carrying a winit user event.
+///
+/// The payload of the custom event is the user event of type `T`.
+pub const USER_EVENT_ID: EventId = EventId("winit/user_event");
+
+// How often gamepads are polled while waiting for events.
+#[cfg(feature="gamepad")]
+const GAMEPAD_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A Piston window using winit.
+///
+/// Window sizes and positions, cursor and touch positions are in logical pixels,
+/// which are scaled by the scale factor of the monitor to physical pixels.
+/// Draw sizes and the software framebuffer are in physical pixels.
+pub struct WinitWindow<T: 'static = ()> {
     // TODO: These public fields should be changed to accessors
-    events_loop: Option<EventLoop<()>>,
+    events_loop: Option<EventLoop<T>>,
     
     #[cfg(feature="use-vulkano")]
     surface: Arc<Surface<OriginalWinitWindow>>,
+
+    // The OpenGL context, declared before the window such that it is dropped first.
+    #[cfg(feature="use-opengl")]
+    ctx: Option<opengl::Context>,
+    #[cfg(feature="use-opengl")]
+    gl: Option<glow::Context>,
+    // The software framebuffer, declared before the window such that it is dropped first.
+    #[cfg(feature="use-software")]
+    framebuffer: Option<Framebuffer>,
     
     /// Winit window.
     #[cfg(not(feature="use-vulkano"))]
@@ -75,67 +126,566 @@ pub struct WinitWindow {
     exit_on_esc: bool,
     should_close: bool,
     automatic_close: bool,
-    queued_events: VecDeque<Event>,
-
-    // Used to fake capturing of cursor,
-    // to get relative mouse events.
-    is_capturing_cursor: bool,
-    // Stores the last known cursor position.
-    last_cursor_pos: Option<[f64; 2]>,
-    // Stores relative coordinates to emit on next poll.
-    mouse_relative: Option<(f64, f64)>,
-    // Used to emit cursor event after enter/leave.
-    cursor_pos: Option<[f64; 2]>,
+    cursor_mode: CursorMode,
+    // The input state, which is kept apart from the winit window.
+    state: state::InputState,
+    // Whether controllers are enabled in the settings.
+    #[cfg(feature="gamepad")]
+    controllers: bool,
+    // The connected gamepads, when enabled.
+    #[cfg(feature="gamepad")]
+    gamepads: Option<gamepad::Gamepads>,
     
     /// Stores list of events ready for processing.
-    pub events: VecDeque<winit::event::Event<'static, ()>>,
+    pub events: VecDeque<winit::event::Event<'static, T>>,
+}
+
+/// An error that occurred while creating a `WinitWindow`.
+#[derive(Debug)]
+pub enum WinitWindowError {
+    /// There is no display server to connect to,
+    /// e.g. when running headless without `DISPLAY` or `WAYLAND_DISPLAY` set.
+    NoDisplay,
+    /// The graphics API requested by the window settings is not supported.
+    UnsupportedGraphicsApi(UnsupportedGraphicsApiError),
+    /// The operating system failed to create the window.
+    Os(winit::error::OsError),
+    /// Failed to create a Vulkan surface for the window.
+    #[cfg(feature="use-vulkano")]
+    Surface(vulkano::swapchain::SurfaceCreationError),
+    /// Failed to create an OpenGL context for the window.
+    #[cfg(feature="use-opengl")]
+    Context(glutin::CreationError),
+}
+
+impl fmt::Display for WinitWindowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WinitWindowError::NoDisplay => write!(f, "No display available to create a window on"),
+            WinitWindowError::UnsupportedGraphicsApi(ref err) => fmt::Display::fmt(err, f),
+            WinitWindowError::Os(ref err) => write!(f, "Failed to create window: {}", err),
+            #[cfg(feature="use-vulkano")]
+            WinitWindowError::Surface(ref err) => write!(f, "Failed to create Vulkan surface: {}", err),
+            #[cfg(feature="use-opengl")]
+            WinitWindowError::Context(ref err) => write!(f, "Failed to create OpenGL context: {}", err),
+        }
+    }
+}
+
+impl Error for WinitWindowError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match *self {
+            WinitWindowError::NoDisplay => None,
+            WinitWindowError::UnsupportedGraphicsApi(ref err) => Some(err),
+            WinitWindowError::Os(ref err) => Some(err),
+            #[cfg(feature="use-vulkano")]
+            WinitWindowError::Surface(ref err) => Some(err),
+            #[cfg(feature="use-opengl")]
+            WinitWindowError::Context(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<UnsupportedGraphicsApiError> for WinitWindowError {
+    fn from(err: UnsupportedGraphicsApiError) -> Self {
+        WinitWindowError::UnsupportedGraphicsApi(err)
+    }
+}
+
+impl From<winit::error::OsError> for WinitWindowError {
+    fn from(err: winit::error::OsError) -> Self {
+        WinitWindowError::Os(err)
+    }
+}
+
+#[cfg(feature="use-vulkano")]
+impl From<vulkano::swapchain::SurfaceCreationError> for WinitWindowError {
+    fn from(err: vulkano::swapchain::SurfaceCreationError) -> Self {
+        WinitWindowError::Surface(err)
+    }
+}
+
+#[cfg(feature="use-opengl")]
+impl From<glutin::CreationError> for WinitWindowError {
+    fn from(err: glutin::CreationError) -> Self {
+        WinitWindowError::Context(err)
+    }
+}
+
+/// How the cursor behaves over the window.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CursorMode {
+    /// The cursor is visible and moves freely.
+    Normal,
+    /// The cursor is hidden while over the window, but moves freely.
+    Hidden,
+    /// The cursor is visible and kept inside the window.
+    /// Absolute cursor positions are emitted as usual.
+    Confined,
+    /// The cursor is hidden and locked to the window.
+    /// Only relative mouse motion is emitted, e.g. for first person cameras.
+    Locked,
+}
+
+/// An error that occurred while setting the cursor mode.
+#[derive(Debug)]
+pub enum CursorModeError {
+    /// The cursor mode is not supported by the platform.
+    Unsupported(CursorMode),
+    /// The window system failed to grab the cursor.
+    Grab(winit::error::ExternalError),
+}
+
+impl fmt::Display for CursorModeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CursorModeError::Unsupported(mode) => write!(f, "Cursor mode {:?} is not supported", mode),
+            CursorModeError::Grab(ref err) => write!(f, "Failed to grab cursor: {}", err),
+        }
+    }
+}
+
+impl Error for CursorModeError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match *self {
+            CursorModeError::Unsupported(_) => None,
+            CursorModeError::Grab(ref err) => Some(err),
+        }
+    }
+}
+
+/// Selects which received characters are emitted as text.
+#[derive(Copy, Clone, Debug)]
+pub enum TextFilter {
+    /// Drops the control characters that SDL does not report as text:
+    /// delete, escape, backspace, carriage return, line feed and tab.
+    Sdl,
+    /// Emits every character.
+    PassThrough,
+    /// Emits the characters for which the predicate returns `true`.
+    Custom(fn(char) -> bool),
+}
+
+impl TextFilter {
+    /// Returns `true` if the character is emitted as text.
+    pub fn accepts(&self, ch: char) -> bool {
+        match *self {
+            TextFilter::Sdl => match ch {
+                '\u{7f}' | // Delete
+                '\u{1b}' | // Escape
+                '\u{8}'  | // Backspace
+                '\r' | '\n' | '\t' => false,
+                _ => true,
+            },
+            TextFilter::PassThrough => true,
+            TextFilter::Custom(f) => f(ch),
+        }
+    }
+}
+
+/// A scroll delta in the unit reported by the device.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScrollDelta {
+    /// Scrolled lines, e.g. by a mouse wheel.
+    Lines([f64; 2]),
+    /// Scrolled logical pixels, e.g. by a touchpad.
+    Pixels([f64; 2]),
+}
+
+/// Converts scroll deltas to the amounts emitted as `Motion::MouseScroll`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScrollPolicy {
+    /// The number of lines scrolled per pixel, for devices that scroll by pixels.
+    pub lines_per_pixel: f64,
+    /// Inverts the scroll direction.
+    pub invert: bool,
+    /// Scales the horizontal and vertical amounts.
+    pub scale: [f64; 2],
+}
+
+impl Default for ScrollPolicy {
+    /// Emits lines unchanged and scrolls a line per 20 pixels,
+    /// which is about the height of a line of text.
+    fn default() -> ScrollPolicy {
+        ScrollPolicy {
+            lines_per_pixel: 0.05,
+            invert: false,
+            scale: [1.0, 1.0],
+        }
+    }
+}
+
+impl ScrollPolicy {
+    /// Returns the scroll amount to emit for a scroll delta.
+    pub fn apply(&self, delta: ScrollDelta) -> [f64; 2] {
+        let [x, y] = match delta {
+            ScrollDelta::Lines(lines) => lines,
+            ScrollDelta::Pixels(pixels) => [pixels[0] * self.lines_per_pixel, pixels[1] * self.lines_per_pixel],
+        };
+        let sign = if self.invert { -1.0 } else { 1.0 };
+        [sign * self.scale[0] * x, sign * self.scale[1] * y]
+    }
+}
+
+/// Returns `true` when grabbing the cursor keeps it inside the window,
+/// instead of locking it in place.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+fn grab_confines(window: &OriginalWinitWindow) -> bool {
+    use winit::platform::unix::WindowExtUnix;
+
+    // Wayland locks the pointer when grabbing.
+    window.xlib_window().is_some()
+}
+
+/// Returns `true` when grabbing the cursor keeps it inside the window,
+/// instead of locking it in place.
+#[cfg(target_os = "windows")]
+fn grab_confines(_window: &OriginalWinitWindow) -> bool {
+    true
+}
+
+/// Returns `true` when grabbing the cursor keeps it inside the window,
+/// instead of locking it in place.
+#[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+              target_os = "netbsd", target_os = "openbsd", target_os = "windows")))]
+fn grab_confines(_window: &OriginalWinitWindow) -> bool {
+    false
+}
+
+/// Returns the graphics APIs this back-end can set up for a window.
+fn supported_graphics_apis() -> Vec<&'static str> {
+    let mut apis = vec![];
+    if cfg!(feature = "use-vulkano") {
+        apis.push("Vulkan");
+    }
+    if cfg!(feature = "use-opengl") {
+        apis.push("OpenGL");
+    }
+    apis
+}
+
+/// Checks that the graphics API requested by the settings, if any, is supported.
+fn check_graphics_api(settings: &WindowSettings) -> Result<(), UnsupportedGraphicsApiError> {
+    if let Some(api) = settings.get_maybe_graphics_api() {
+        let expected = supported_graphics_apis();
+        if !expected.contains(&api.api) {
+            return Err(UnsupportedGraphicsApiError {
+                found: api.api.into(),
+                expected: expected.into_iter().map(|api| api.into()).collect(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Returns `false` when there is no display server to connect to.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+fn has_display() -> bool {
+    use std::env::var_os;
+
+    var_os("DISPLAY").is_some() || var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// Returns `false` when there is no display server to connect to.
+#[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+              target_os = "netbsd", target_os = "openbsd")))]
+fn has_display() -> bool {
+    true
+}
+
+/// Creates an event loop, returning `None` when there is no display.
+///
+/// Winit panics when it fails to connect to the display server,
+/// so the environment is checked first.
+fn create_event_loop<T: 'static>() -> Option<EventLoop<T>> {
+    if !has_display() {
+        return None;
+    }
+    Some(EventLoop::with_user_event())
+}
+
+/// Converts a physical window size to logical pixels.
+fn window_size(size: PhysicalSize<u32>, scale_factor: f64) -> [f64; 2] {
+    let size: LogicalSize<f64> = size.to_logical(scale_factor);
+    [size.width, size.height]
+}
+
+/// Converts a logical window size to physical pixels, rounded to whole pixels.
+fn physical_size(size: Size, scale_factor: f64) -> PhysicalSize<u32> {
+    LogicalSize::new(size.width, size.height).to_physical(scale_factor)
+}
+
+/// Converts a physical window position to logical pixels, rounded to whole pixels.
+fn window_position(pos: PhysicalPosition<i32>, scale_factor: f64) -> Position {
+    let pos: LogicalPosition<f64> = pos.to_logical(scale_factor);
+    Position { x: pos.x.round() as i32, y: pos.y.round() as i32 }
+}
+
+/// Converts a logical window position to physical pixels.
+fn physical_position(pos: Position, scale_factor: f64) -> PhysicalPosition<i32> {
+    LogicalPosition::new(pos.x as f64, pos.y as f64).to_physical(scale_factor)
+}
+
+/// Returns the center of a window with a physical size, in logical pixels.
+fn window_center(size: PhysicalSize<u32>, scale_factor: f64) -> [f64; 2] {
+    let size = window_size(size, scale_factor);
+    [size[0] / 2.0, size[1] / 2.0]
+}
+
+/// Converts a physical cursor or touch position to logical pixels.
+fn cursor_position(pos: PhysicalPosition<f64>, scale_factor: f64) -> [f64; 2] {
+    let pos: LogicalPosition<f64> = pos.to_logical(scale_factor);
+    [pos.x, pos.y]
+}
+
+/// Converts raw mouse motion to logical pixels.
+///
+/// Raw deltas are unaccelerated device units, which match physical pixels for most mice.
+fn motion_delta(delta: (f64, f64), scale_factor: f64) -> [f64; 2] {
+    [delta.0 / scale_factor, delta.1 / scale_factor]
+}
+
+/// Formats a winit event for logging,
+/// without requiring the user event to implement `Debug`.
+struct DebugEvent<'a, 'b: 'a, T: 'static>(&'a WinitEvent<'b, T>);
+
+impl<'a, 'b: 'a, T: 'static> fmt::Debug for DebugEvent<'a, 'b, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self.0 {
+            WinitEvent::NewEvents(ref cause) => write!(f, "NewEvents({:?})", cause),
+            WinitEvent::WindowEvent { ref event, .. } => write!(f, "WindowEvent({:?})", event),
+            WinitEvent::DeviceEvent { ref event, .. } => write!(f, "DeviceEvent({:?})", event),
+            WinitEvent::UserEvent(_) => write!(f, "UserEvent"),
+            WinitEvent::Suspended => write!(f, "Suspended"),
+            WinitEvent::Resumed => write!(f, "Resumed"),
+            WinitEvent::MainEventsCleared => write!(f, "MainEventsCleared"),
+            WinitEvent::RedrawRequested(ref id) => write!(f, "RedrawRequested({:?})", id),
+            WinitEvent::RedrawEventsCleared => write!(f, "RedrawEventsCleared"),
+            WinitEvent::LoopDestroyed => write!(f, "LoopDestroyed"),
+        }
+    }
+}
+
+/// Warns about settings that have no effect with the enabled features.
+///
+/// Only settings changed from their defaults are warned about.
+fn warn_ignored_settings(settings: &WindowSettings) {
+    if !cfg!(feature = "use-opengl") {
+        if settings.get_vsync() {
+            warn!("vsync is ignored without an OpenGL context");
+        }
+        if settings.get_samples() != 0 {
+            warn!("samples={} is ignored without an OpenGL context", settings.get_samples());
+        }
+        if !settings.get_srgb() {
+            warn!("srgb=false is ignored without an OpenGL context");
+        }
+    }
+}
+
+/// Creates a window builder from the window settings.
+///
+/// The size, title, fullscreen, resizable, decorated and transparent
+/// settings are applied to the builder.
+fn window_builder<T: 'static>(settings: &WindowSettings, events_loop: &EventLoop<T>) -> WindowBuilder {
+    use winit::window::Fullscreen;
+
+    warn_ignored_settings(settings);
+
+    let size = settings.get_size();
+    let fullscreen = if settings.get_fullscreen() {
+        Some(Fullscreen::Borderless(events_loop.primary_monitor()))
+    } else {
+        None
+    };
+    WindowBuilder::new()
+        .with_inner_size(LogicalSize::new(size.width, size.height))
+        .with_title(settings.get_title())
+        .with_fullscreen(fullscreen)
+        .with_resizable(settings.get_resizable())
+        .with_decorations(settings.get_decorated())
+        .with_transparent(settings.get_transparent())
 }
 
 impl WinitWindow {
-    
+    /// Creates a new window.
+    ///
+    /// Panics if the window can not be created, see `try_new`.
     #[cfg(not(feature="use-vulkano"))]
     pub fn new(settings: &WindowSettings) -> Self {
-        let events_loop = EventLoop::new();
-        let window = WindowBuilder::new()
-            .with_inner_size(LogicalSize::new(settings.get_size().width, settings.get_size().height))
-            .with_title(settings.get_title())
-            .build(&events_loop)
-            .unwrap();
+        WinitWindow::try_new(settings).unwrap()
+    }
+
+    /// Creates a new window, returning an error if this fails.
+    ///
+    /// The window settings are applied as follows:
+    ///
+    /// - The size, title, fullscreen, resizable, decorated and transparent settings
+    ///   are applied to the window.
+    /// - The graphics API must be one of the enabled features,
+    ///   otherwise `WinitWindowError::UnsupportedGraphicsApi` is returned.
+    /// - Vsync, samples and srgb are applied to the OpenGL context
+    ///   when the `use-opengl` feature is enabled.
+    ///   Otherwise they are ignored, with a warning when changed from their defaults.
+    /// - Controllers are polled by `poll_event` when the `gamepad` feature is enabled,
+    ///   see `disable_gamepads`. Otherwise they are ignored.
+    ///
+    /// Returns `WinitWindowError::NoDisplay` when no display server is configured on Unix,
+    /// i.e. neither `DISPLAY` nor `WAYLAND_DISPLAY` is set.
+    /// Winit panics when a configured display server can not be reached.
+    #[cfg(not(feature="use-vulkano"))]
+    pub fn try_new(settings: &WindowSettings) -> Result<Self, WinitWindowError> {
+        WinitWindow::try_with_user_event(settings)
+    }
+
+    /// Creates a new window with a Vulkan surface.
+    ///
+    /// Panics if the window can not be created, see `try_new_vulkano`.
+    #[cfg(feature="use-vulkano")]
+    pub fn new_vulkano(instance: Arc<Instance>, settings: &WindowSettings) -> Self {
+        WinitWindow::try_new_vulkano(instance, settings).unwrap()
+    }
+
+    /// Creates a new window with a Vulkan surface, returning an error if this fails.
+    ///
+    /// The window settings are applied as follows:
+    ///
+    /// - The size, title, fullscreen, resizable, decorated and transparent settings
+    ///   are applied to the window.
+    /// - The graphics API must be one of the enabled features,
+    ///   otherwise `WinitWindowError::UnsupportedGraphicsApi` is returned.
+    /// - Vsync, samples and srgb are ignored, with a warning when changed from their defaults.
+    ///   They are configured when creating the swapchain instead.
+    /// - Controllers are polled by `poll_event` when the `gamepad` feature is enabled,
+    ///   see `disable_gamepads`. Otherwise they are ignored.
+    ///
+    /// Returns `WinitWindowError::NoDisplay` when no display server is configured on Unix,
+    /// i.e. neither `DISPLAY` nor `WAYLAND_DISPLAY` is set.
+    /// Winit panics when a configured display server can not be reached.
+    #[cfg(feature="use-vulkano")]
+    pub fn try_new_vulkano(
+        instance: Arc<Instance>,
+        settings: &WindowSettings
+    ) -> Result<Self, WinitWindowError> {
+        WinitWindow::try_vulkano_with_user_event(instance, settings)
+    }
+}
 
+impl<T: 'static> WinitWindow<T> {
+    /// Creates a window with an event loop accepting user events of type `T`.
+    ///
+    /// Use `create_proxy` to send user events from other threads.
+    /// Panics if the window can not be created, see `try_with_user_event`.
+    #[cfg(not(feature="use-vulkano"))]
+    pub fn with_user_event(settings: &WindowSettings) -> Self {
+        WinitWindow::try_with_user_event(settings).unwrap()
+    }
+
+    /// Creates a window with an event loop accepting user events of type `T`,
+    /// returning an error if this fails.
+    ///
+    /// The window settings are applied like in `try_new`.
+    #[cfg(not(feature="use-vulkano"))]
+    pub fn try_with_user_event(settings: &WindowSettings) -> Result<Self, WinitWindowError> {
+        check_graphics_api(settings)?;
+        let events_loop = create_event_loop().ok_or(WinitWindowError::NoDisplay)?;
+        let builder = window_builder(settings, &events_loop);
+        // The OpenGL context can only be made current on a window with its visual.
+        #[cfg(all(feature="use-opengl", any(target_os = "linux", target_os = "dragonfly",
+                  target_os = "freebsd", target_os = "netbsd", target_os = "openbsd")))]
+        let visual = opengl::X11Visual::choose(settings, &events_loop);
+        #[cfg(all(feature="use-opengl", any(target_os = "linux", target_os = "dragonfly",
+                  target_os = "freebsd", target_os = "netbsd", target_os = "openbsd")))]
+        let builder = match visual {
+            Some(ref visual) => visual.apply(builder),
+            None => builder,
+        };
+        let window = builder.build(&events_loop)?;
+        let mut window = WinitWindow::new_with_window(settings, window);
+        window.events_loop = Some(events_loop);
+        #[cfg(feature="use-opengl")]
+        {
+            window.ctx = opengl::create_context(window.get_window(), settings)?;
+            window.gl = window.ctx.as_ref().map(opengl::load_gl);
+        }
+        Ok(window)
+    }
+
+    /// Creates a window with a Vulkan surface and an event loop
+    /// accepting user events of type `T`, returning an error if this fails.
+    #[cfg(feature="use-vulkano")]
+    pub fn try_vulkano_with_user_event(
+        instance: Arc<Instance>,
+        settings: &WindowSettings
+    ) -> Result<Self, WinitWindowError> {
+        check_graphics_api(settings)?;
+        let events_loop = create_event_loop().ok_or(WinitWindowError::NoDisplay)?;
+        let window = window_builder(settings, &events_loop).build(&events_loop)?;
+        let surface = surface::create_surface(instance, window)?;
+        let mut window = WinitWindow::new_with_surface(settings, surface);
+        window.events_loop = Some(events_loop);
+        Ok(window)
+    }
+
+    /// Creates a window from an existing winit window.
+    ///
+    /// The window does not own an event loop,
+    /// so events must be pushed to `events` by the caller.
+    #[cfg(not(feature="use-vulkano"))]
+    pub fn new_with_window(settings: &WindowSettings, window: OriginalWinitWindow) -> Self {
         WinitWindow {
-            window,
-            events_loop: Some(events_loop),
+            #[cfg(feature="use-opengl")]
+            ctx: None,
+            #[cfg(feature="use-opengl")]
+            gl: None,
+            #[cfg(feature="use-software")]
+            framebuffer: None,
+            window: window,
+            events_loop: None,
 
             title: settings.get_title(),
             exit_on_esc: settings.get_exit_on_esc(),
             should_close: false,
             automatic_close: settings.get_automatic_close(),
-            queued_events: VecDeque::new(),
-
-            cursor_pos: None,
-            is_capturing_cursor: false,
-            last_cursor_pos: None,
-            mouse_relative: None,
+            cursor_mode: CursorMode::Normal,
+            state: state::InputState::new(),
+            #[cfg(feature="gamepad")]
+            controllers: settings.get_controllers(),
+            #[cfg(feature="gamepad")]
+            gamepads: if settings.get_controllers() { gamepad::Gamepads::new() } else { None },
 
             events: VecDeque::new(),
         }
     }
 
-    pub fn new_with_window(settings: &WindowSettings, window: OriginalWinitWindow) -> Self {
+    /// Creates a window from an existing Vulkan surface.
+    ///
+    /// The window does not own an event loop,
+    /// so events must be pushed to `events` by the caller.
+    #[cfg(feature="use-vulkano")]
+    pub fn new_with_surface(settings: &WindowSettings, surface: Arc<Surface<OriginalWinitWindow>>) -> Self {
         WinitWindow {
-            window: window,
+            #[cfg(feature="use-opengl")]
+            ctx: None,
+            #[cfg(feature="use-opengl")]
+            gl: None,
+            #[cfg(feature="use-software")]
+            framebuffer: None,
+            surface: surface,
             events_loop: None,
 
             title: settings.get_title(),
             exit_on_esc: settings.get_exit_on_esc(),
             should_close: false,
             automatic_close: settings.get_automatic_close(),
-            queued_events: VecDeque::new(),
-
-            cursor_pos: None,
-            is_capturing_cursor: false,
-            last_cursor_pos: None,
-            mouse_relative: None,
+            cursor_mode: CursorMode::Normal,
+            state: state::InputState::new(),
+            #[cfg(feature="gamepad")]
+            controllers: settings.get_controllers(),
+            #[cfg(feature="gamepad")]
+            gamepads: if settings.get_controllers() { gamepad::Gamepads::new() } else { None },
 
             events: VecDeque::new(),
         }
@@ -146,200 +696,472 @@ impl WinitWindow {
         &self.window
     }
 
+    #[cfg(feature="use-vulkano")]
+    pub fn get_window(&self) -> &OriginalWinitWindow {
+        self.surface.window()
+    }
+
+    /// Returns the Vulkan surface of the window.
+    #[cfg(feature="use-vulkano")]
+    pub fn surface(&self) -> &Arc<Surface<OriginalWinitWindow>> {
+        &self.surface
+    }
+
+    /// Returns the OpenGL functions of the window's context.
+    ///
+    /// Returns `None` when no OpenGL context was requested.
+    #[cfg(feature="use-opengl")]
+    pub fn gl(&self) -> Option<&glow::Context> {
+        self.gl.as_ref()
+    }
+
+    /// Enables software presentation with a CPU framebuffer.
+    ///
+    /// The framebuffer is presented to the window by `swap_buffers`,
+    /// so no graphics API is required for rendering.
+    #[cfg(feature="use-software")]
+    pub fn enable_framebuffer(&mut self) -> Result<(), FramebufferError> {
+        if self.framebuffer.is_none() {
+            self.framebuffer = Some(Framebuffer::new(self.get_window())?);
+        }
+        Ok(())
+    }
+
+    /// Returns the software framebuffer.
+    ///
+    /// Returns `None` unless enabled with `enable_framebuffer`.
+    #[cfg(feature="use-software")]
+    pub fn framebuffer(&self) -> Option<&Framebuffer> {
+        self.framebuffer.as_ref()
+    }
+
+    /// Returns the software framebuffer for writing pixels.
+    ///
+    /// Returns `None` unless enabled with `enable_framebuffer`.
+    #[cfg(feature="use-software")]
+    pub fn framebuffer_mut(&mut self) -> Option<&mut Framebuffer> {
+        self.framebuffer.as_mut()
+    }
+
     /// Get the event loop to break out event handling
-    pub fn events_loop(&mut self) -> EventLoop<()> { 
+    pub fn events_loop(&mut self) -> EventLoop<T> { 
         self.events_loop.take().unwrap()
     }
 
+    /// Creates a proxy for sending user events to the event loop,
+    /// waking up `wait_event` from other threads.
+    ///
+    /// Returns `None` when the event loop has been taken.
+    pub fn create_proxy(&self) -> Option<EventLoopProxy<T>> {
+        self.events_loop.as_ref().map(|events_loop| events_loop.create_proxy())
+    }
+
+    /// Returns whether untranslated winit events are logged.
+    pub fn get_trace_events(&self) -> bool {
+        self.state.trace_events
+    }
+
+    /// Sets whether untranslated winit events are logged.
+    ///
+    /// When enabled, every event that `handle_event` flags as unknown
+    /// is logged at debug level.
+    pub fn set_trace_events(&mut self, value: bool) {
+        self.state.trace_events = value;
+    }
+
+    /// Returns the modifier keys currently held down.
+    ///
+    /// The state is updated as events are polled,
+    /// so it matches the button event that was polled last.
+    /// Modifiers are released when the window loses focus.
+    pub fn get_modifiers(&self) -> keyboard::ModifierKey {
+        self.state.modifiers
+    }
+
+    /// Returns whether key presses repeated by the OS are emitted.
+    pub fn get_key_repeat(&self) -> bool {
+        self.state.key_repeat
+    }
+
+    /// Sets whether key presses repeated by the OS are emitted.
+    ///
+    /// When disabled, only the initial press of a held key is emitted.
+    /// Enabled by default.
+    pub fn set_key_repeat(&mut self, value: bool) {
+        self.state.key_repeat = value;
+    }
+
+    /// Returns `true` if the last key press was repeated by the OS
+    /// while the key was held down.
+    ///
+    /// Like `get_modifiers`, this matches the button event that was polled last.
+    pub fn is_repeat(&self) -> bool {
+        self.state.is_repeat
+    }
+
+    /// Returns whether the primary touch emulates the mouse.
+    pub fn get_touch_mouse(&self) -> bool {
+        self.state.touch_mouse
+    }
+
+    /// Sets whether the primary touch emulates the mouse.
+    ///
+    /// When enabled, the first finger to touch the window also emits cursor motion
+    /// and presses the left mouse button, until it is lifted.
+    /// Touch events are emitted as usual.
+    pub fn set_touch_mouse(&mut self, value: bool) {
+        self.state.touch_mouse = value;
+        if !value {
+            self.state.primary_touch = None;
+        }
+    }
+
+    /// Returns the policy for converting scroll deltas.
+    pub fn get_scroll_policy(&self) -> ScrollPolicy {
+        self.state.scroll_policy
+    }
+
+    /// Sets the policy for converting scroll deltas.
+    ///
+    /// Mouse wheels scroll by lines and touchpads often by pixels,
+    /// so `lines_per_pixel` brings pixel deltas to a comparable amount.
+    /// The default scrolls a line per 20 pixels;
+    /// set `lines_per_pixel` to 1.0 to emit pixels unchanged.
+    pub fn set_scroll_policy(&mut self, policy: ScrollPolicy) {
+        self.state.scroll_policy = policy;
+    }
+
+    /// Returns the delta of the last scroll, in the unit reported by the device.
+    ///
+    /// Like `get_modifiers`, this matches the scroll event that was polled last.
+    /// The delta is not converted by the scroll policy.
+    pub fn last_scroll(&self) -> Option<ScrollDelta> {
+        self.state.last_scroll
+    }
+
+    /// Returns the mouse button that a winit `MouseButton::Other` code is mapped to,
+    /// if the mapping is overridden.
+    pub fn get_mouse_override(&self, code: u8) -> Option<MouseButton> {
+        self.state.mouse_overrides.get(&code).cloned()
+    }
+
+    /// Overrides the mouse button that a winit `MouseButton::Other` code is mapped to,
+    /// or restores the mapping of `map_mouse` with `None`.
+    ///
+    /// This makes buttons bindable that `map_mouse` maps to `Unknown`,
+    /// e.g. the thumb buttons of some mice.
+    pub fn set_mouse_override(&mut self, code: u8, button: Option<MouseButton>) {
+        match button {
+            Some(button) => { self.state.mouse_overrides.insert(code, button); }
+            None => { self.state.mouse_overrides.remove(&code); }
+        }
+    }
+
+    /// Maps Piston's mouse button to winit's mouse button,
+    /// like `unmap_mouse` but following the overrides of this window.
+    pub fn unmap_mouse(&self, mouse_button: MouseButton) -> Option<WinitMouseButton> {
+        self.state.unmap_mouse(mouse_button)
+    }
+
+    /// Starts polling gamepads in `poll_event` again, after `disable_gamepads`.
+    ///
+    /// Returns `false` when controllers are disabled in the window settings
+    /// or gamepads are not supported on this platform.
+    #[cfg(feature="gamepad")]
+    pub fn enable_gamepads(&mut self) -> bool {
+        if self.controllers && self.gamepads.is_none() {
+            self.gamepads = gamepad::Gamepads::new();
+        }
+        self.gamepads.is_some()
+    }
+
+    /// Stops polling gamepads.
+    ///
+    /// Gamepads are polled from the start when controllers are enabled in the window settings,
+    /// which is the default.
+    /// They do not wake up the event loop, so while they are polled,
+    /// `wait_event` wakes up every 10 milliseconds.
+    /// Disabling them lets the event loop sleep until the next window event.
+    #[cfg(feature="gamepad")]
+    pub fn disable_gamepads(&mut self) {
+        self.gamepads = None;
+    }
+
+    /// Returns the thresholds for recognizing gestures,
+    /// or `None` when gestures are disabled.
+    pub fn get_gesture_settings(&self) -> Option<GestureSettings> {
+        self.state.gestures.as_ref().map(|gestures| gestures.settings)
+    }
+
+    /// Enables gestures with the given thresholds, or disables them with `None`.
+    ///
+    /// Gestures are recognized from the touch inputs of `handle_event`.
+    /// `poll_event` emits them as custom events with id `GESTURE_EVENT_ID`,
+    /// following the touch input that completed them.
+    /// Callers of `handle_event` read them with `pop_gesture` instead.
+    /// Disabled by default.
+    pub fn set_gesture_settings(&mut self, settings: Option<GestureSettings>) {
+        match (settings, self.state.gestures.as_mut()) {
+            (Some(settings), Some(gestures)) => gestures.settings = settings,
+            (settings, _) => self.state.gestures = settings.map(gesture::GestureRecognizer::new),
+        }
+    }
+
+    /// Pops the next recognized gesture.
+    ///
+    /// Returns `None` when gestures are disabled.
+    pub fn pop_gesture(&mut self) -> Option<Gesture> {
+        self.state.pop_gesture()
+    }
+
+    /// Returns the filter for received characters.
+    pub fn get_text_filter(&self) -> TextFilter {
+        self.state.text_filter
+    }
+
+    /// Sets the filter for received characters.
+    ///
+    /// Defaults to `TextFilter::Sdl`, for compatibility with other window backends.
+    pub fn set_text_filter(&mut self, filter: TextFilter) {
+        self.state.text_filter = filter;
+    }
+
+    /// Sets the position of the IME candidate window, in logical pixels
+    /// relative to the top left corner of the window.
+    ///
+    /// Has no effect on Wayland, iOS and the web.
+    pub fn set_ime_position(&self, pos: [f64; 2]) {
+        self.get_window().set_ime_position(LogicalPosition::new(pos[0], pos[1]));
+    }
+
+    /// Returns the current cursor mode.
+    pub fn get_cursor_mode(&self) -> CursorMode {
+        self.cursor_mode
+    }
+
+    /// Returns `true` if the cursor mode is supported by the platform.
+    ///
+    /// `Confined` requires a window system where grabbing the cursor confines it,
+    /// which is the case for X11 and Windows.
+    /// `Locked` falls back to warping the cursor to the window center
+    /// when grabbing fails, and is supported except on the web.
+    pub fn supports_cursor_mode(&self, mode: CursorMode) -> bool {
+        match mode {
+            CursorMode::Normal | CursorMode::Hidden => true,
+            CursorMode::Confined => grab_confines(self.get_window()),
+            CursorMode::Locked => cfg!(not(target_arch = "wasm32")),
+        }
+    }
+
+    /// Sets the cursor mode.
+    ///
+    /// In `Locked` mode, relative motion is in logical pixels.
+    /// When the cursor is grabbed it comes from raw device motion without acceleration,
+    /// otherwise from warping the cursor, so sensitivity may differ slightly between the two.
+    ///
+    /// On failure, the cursor mode is reset to `Normal`.
+    pub fn set_cursor_mode(&mut self, mode: CursorMode) -> Result<(), CursorModeError> {
+        if !self.supports_cursor_mode(mode) {
+            return Err(CursorModeError::Unsupported(mode));
+        }
+
+        if self.state.cursor_grabbed {
+            let _ = self.get_window().set_cursor_grab(false);
+            self.state.cursor_grabbed = false;
+        }
+        // Relative motion is only valid within a mode.
+        self.state.mouse_relative = None;
+        self.cursor_mode = mode;
+        self.state.is_capturing_cursor = mode == CursorMode::Locked;
+        self.get_window().set_cursor_visible(mode == CursorMode::Normal || mode == CursorMode::Confined);
+
+        match mode {
+            CursorMode::Normal | CursorMode::Hidden => {}
+            CursorMode::Confined => {
+                if let Err(err) = self.get_window().set_cursor_grab(true) {
+                    self.cursor_mode = CursorMode::Normal;
+                    return Err(CursorModeError::Grab(err));
+                }
+                self.state.cursor_grabbed = true;
+            }
+            CursorMode::Locked => {
+                // Relative motion is read from raw device events.
+                // When grabbing fails, the capturing of cursor is faked
+                // by setting the position to the center of window.
+                match self.get_window().set_cursor_grab(true) {
+                    Ok(()) => self.state.cursor_grabbed = true,
+                    Err(err) => {
+                        debug!("cursor grab failed, faking capture: {}", err);
+                        self.fake_capture();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Convert an incoming winit event to Piston input.
     /// Update cursor state if necessary.
     ///
-    /// The `unknown` flag is set to `true` when the event is not recognized.
+    /// The `unknown` flag is set to `true` when the event is not emitted as input,
+    /// either because it is not recognized or because it only updates the window state,
+    /// e.g. modifier changes or characters rejected by the text filter.
     /// This is used to poll another event to make the event loop logic sound.
     /// When `unknown` is `true`, the return value is `None`.
-    pub fn handle_event(&mut self, ev: &winit::event::Event<()>, unknown: &mut bool) -> Option<Input> {
-        use winit::event::Event as E;
-        use winit::event::WindowEvent as WE;
-        use winit::event::MouseScrollDelta;
-        use input::{ Key, Motion };
-
-        match ev {
-            E::WindowEvent {
-                event: WE::Resized(ref size), ..
-            } => {
-                let draw_size = self.draw_size();
-                Some(Input::Resize(ResizeArgs {
-                    window_size: [size.width as f64, size.height as f64],
-                    draw_size: draw_size.into(),
-                }))
-            },
-            
-            E::WindowEvent {
-                event: WE::ReceivedCharacter(ref ch), ..
-            } => {
-                let string = match ch {
-                    // Ignore control characters and return ascii for Text event (like sdl2).
-                    '\u{7f}' | // Delete
-                    '\u{1b}' | // Escape
-                    '\u{8}'  | // Backspace
-                    '\r' | '\n' | '\t' => "".to_string(),
-                    _ => ch.to_string()
-                };
-                Some(Input::Text(string))
-            },
-            E::WindowEvent {
-                event: WE::Focused(ref focused), ..
-            } =>
-                Some(Input::Focus(*focused)),
-            E::WindowEvent {
-                event: WE::KeyboardInput{
-                    input: winit::event::KeyboardInput{
-                        state: winit::event::ElementState::Pressed,
-                        virtual_keycode: Some(ref key), ref scancode, ..
-                    }, ..
-                }, ..
-            } => {
-                println!("winit key press: {:?}", key);
-                let piston_key = map_key(*key);
-                if let (true, Key::Escape) = (self.exit_on_esc, piston_key) {
-                    self.should_close = true;
-                }
-                Some(Input::Button(ButtonArgs {
-                    state: ButtonState::Press,
-                    button: Button::Keyboard(piston_key),
-                    scancode: Some(*scancode as i32),
-                }))
-            },
-            E::WindowEvent {
-                 event: WE::KeyboardInput{
-                     input: winit::event::KeyboardInput{
-                         state: winit::event::ElementState::Released,
-                         virtual_keycode: Some(ref key), ref scancode, ..
-                     }, ..
-                 }, ..
-             } => {
-                println!("winit key release: {:?}", key);
-                Some(Input::Button(ButtonArgs {
-                    state: ButtonState::Release,
-                    button: Button::Keyboard(map_key(*key)),
-                    scancode: Some(*scancode as i32),
-                }))},
-            E::WindowEvent {
-                event: WE::Touch(winit::event::Touch { ref phase, ref location, ref id, .. }), ..
-            } => {
-                use winit::event::TouchPhase;
-                use input::{Touch, TouchArgs};
-
-                Some(Input::Move(Motion::Touch(TouchArgs::new(
-                    0, *id as i64, [location.x, location.y], 1.0, match phase {
-                        TouchPhase::Started => Touch::Start,
-                        TouchPhase::Moved => Touch::Move,
-                        TouchPhase::Ended => Touch::End,
-                        TouchPhase::Cancelled => Touch::Cancel
+    pub fn handle_event(&mut self, ev: &winit::event::Event<T>, unknown: &mut bool) -> Option<Input> {
+        let scale_factor = self.get_window().scale_factor();
+        let input = self.state.handle_event(ev, scale_factor, unknown);
+        if let Some(ref input) = input {
+            self.apply_input(input);
+        }
+        input
+    }
+
+    // Applies the effects of an emitted input on the window.
+    fn apply_input(&mut self, input: &Input) {
+        match *input {
+            #[cfg(any(feature="use-opengl", feature="use-software"))]
+            Input::Resize(ref args) => {
+                let [width, height] = args.draw_size;
+                #[cfg(feature="use-opengl")]
+                {
+                    if let Some(ref ctx) = self.ctx {
+                        ctx.resize(glutin::dpi::PhysicalSize::new(width, height));
                     }
-                ))))
-            },
-            E::WindowEvent {
-                event: WE::CursorMoved{ref position, ..}, ..
-            } => {
-                let x = position.x as f64 / self.window.scale_factor();
-                let y = position.y as f64 / self.window.scale_factor();
-
-                if let Some(pos) = self.last_cursor_pos {
-                    let dx = x - pos[0];
-                    let dy = y - pos[1];
-                    if self.is_capturing_cursor {
-                        self.last_cursor_pos = Some([x as f64, y as f64]);
-                        self.fake_capture();
-                        // Skip normal mouse movement and emit relative motion only.
-                        return Some(Input::Move(Motion::MouseRelative([dx as f64, dy as f64])));
+                }
+                #[cfg(feature="use-software")]
+                {
+                    if let Some(ref mut framebuffer) = self.framebuffer {
+                        framebuffer.resize(width, height);
                     }
-                    // Send relative mouse movement next time.
-                    self.mouse_relative = Some((dx as f64, dy as f64));
                 }
-
-                self.last_cursor_pos = Some([x as f64, y as f64]);
-                Some(Input::Move(Motion::MouseCursor([x as f64, y as f64])))
             }
-            E::WindowEvent {
-                event: WE::CursorEntered{..}, ..
-            } => Some(Input::Cursor(true)),
-            E::WindowEvent {
-                event: WE::CursorLeft{..}, ..
-            } => Some(Input::Cursor(false)),
-            E::WindowEvent {
-                event: WE::MouseWheel{delta: MouseScrollDelta::PixelDelta(ref pos), ..}, ..
-            } => Some(Input::Move(Motion::MouseScroll([pos.x as f64, pos.y as f64]))),
-            E::WindowEvent {
-                event: WE::MouseWheel{delta: MouseScrollDelta::LineDelta(ref x, ref y), ..}, ..
-            } => Some(Input::Move(Motion::MouseScroll([*x as f64, *y as f64]))),
-            E::WindowEvent {
-                event: WE::MouseInput{state: winit::event::ElementState::Pressed, ref button, ..}, ..
-            } => Some(Input::Button(ButtonArgs {
-                state: ButtonState::Press,
-                button: Button::Mouse(map_mouse(*button)),
-                scancode: None,
-            })),
-            E::WindowEvent {
-                event: WE::MouseInput{state: winit::event::ElementState::Released, ref button, ..}, ..
-            } => Some(Input::Button(ButtonArgs {
-                state: ButtonState::Release,
-                button: Button::Mouse(map_mouse(*button)),
-                scancode: None,
-            })),
-            E::WindowEvent {
-                event: WE::HoveredFile(ref path), ..
-            } => Some(Input::FileDrag(FileDrag::Hover(path.clone()))),
-            E::WindowEvent {
-                event: WE::DroppedFile(ref path), ..
-            } => Some(Input::FileDrag(FileDrag::Drop(path.clone()))),
-            E::WindowEvent {
-                event: WE::HoveredFileCancelled, ..
-            } => Some(Input::FileDrag(FileDrag::Cancel)),
-            E::WindowEvent { event: WE::CloseRequested, .. } => {
-                if self.automatic_close {
+            Input::Button(ButtonArgs {
+                state: ButtonState::Press, button: Button::Keyboard(Key::Escape), ..
+            }) => {
+                if self.exit_on_esc {
                     self.should_close = true;
                 }
-                Some(Input::Close(CloseArgs))
             }
-            _ => {
-                *unknown = true;
-                None
+            Input::Close(_) => {
+                if self.automatic_close {
+                    self.should_close = true;
+                }
             }
+            _ => {}
+        }
+        if self.state.recenter_cursor {
+            self.state.recenter_cursor = false;
+            self.fake_capture();
         }
     }
     
-    // These events are emitted before popping a new event from the queue.
-    // This is because Piston handles some events separately.
-    fn pre_pop_front_event(&mut self) -> Option<Input> {
-        use input::Motion;
-
-        // Check for a pending mouse cursor move event.
-        if let Some(pos) = self.cursor_pos {
-            self.cursor_pos = None;
-            return Some(Input::Move(Motion::MouseCursor(pos)));
+    /// Pumps the winit event loop and pushes the received events
+    /// to the back of `events`.
+    ///
+    /// With `ControlFlow::Poll` this returns as soon as pending events are received.
+    /// With `ControlFlow::Wait` or `ControlFlow::WaitUntil` it blocks until
+    /// at least one event is received or the deadline is reached.
+    ///
+    /// Does nothing when the event loop has been taken with `events_loop`,
+    /// in which case the caller is responsible for filling `events`.
+    #[cfg(any(target_os = "windows", target_os = "macos",
+              target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+              target_os = "netbsd", target_os = "openbsd"))]
+    fn pump_events(&mut self, wait: ControlFlow) {
+        use winit::event::{StartCause, WindowEvent};
+        use winit::platform::desktop::EventLoopExtDesktop;
+
+        let wait = self.bound_wait(wait);
+        let events = &mut self.events;
+        if let Some(ref mut events_loop) = self.events_loop {
+            events_loop.run_return(|ev, _, control_flow| {
+                match ev {
+                    // `Exit` is sticky, so a reached deadline always returns.
+                    WinitEvent::NewEvents(StartCause::ResumeTimeReached {..}) =>
+                        *control_flow = ControlFlow::Exit,
+                    WinitEvent::MainEventsCleared => {
+                        *control_flow = if wait == ControlFlow::Poll || events.len() > 0 {
+                            ControlFlow::Exit
+                        } else {
+                            wait
+                        };
+                    }
+                    // The logical size changes with the scale factor,
+                    // so the new size is reported as a resize.
+                    WinitEvent::WindowEvent {
+                        window_id,
+                        event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+                    } => events.push_back(WinitEvent::WindowEvent {
+                        window_id: window_id,
+                        event: WindowEvent::Resized(*new_inner_size),
+                    }),
+                    WinitEvent::NewEvents(_) |
+                    WinitEvent::RedrawRequested(_) |
+                    WinitEvent::RedrawEventsCleared |
+                    WinitEvent::LoopDestroyed => {}
+                    ev => {
+                        // Events borrowing from the event loop can not be queued.
+                        if let Some(ev) = ev.to_static() {
+                            events.push_back(ev);
+                        }
+                    }
+                }
+            });
         }
+    }
 
-        // Check for a pending relative mouse move event.
-        if let Some((x, y)) = self.mouse_relative {
-            self.mouse_relative = None;
-            return Some(Input::Move(Motion::MouseRelative([x, y])));
+    /// Does nothing, because the event loop can only be pumped on desktop platforms.
+    ///
+    /// Elsewhere the event loop is run by the caller,
+    /// which pushes the received events to `events`.
+    #[cfg(not(any(target_os = "windows", target_os = "macos",
+                  target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+                  target_os = "netbsd", target_os = "openbsd")))]
+    fn pump_events(&mut self, _wait: ControlFlow) {}
+
+    // Returns `true` when `pump_events` can wait for events.
+    fn can_pump(&self) -> bool {
+        cfg!(any(target_os = "windows", target_os = "macos",
+                 target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+                 target_os = "netbsd", target_os = "openbsd")) &&
+        self.events_loop.is_some()
+    }
+
+    // Gamepads and long presses do not wake up the event loop,
+    // so waiting is bounded to poll them in time.
+    fn bound_wait(&self, wait: ControlFlow) -> ControlFlow {
+        let deadline = self.state.gestures.as_ref().and_then(|gestures| gestures.deadline());
+        #[cfg(feature="gamepad")]
+        let deadline = if self.gamepads.is_some() {
+            let poll = Instant::now() + GAMEPAD_POLL_INTERVAL;
+            Some(deadline.map_or(poll, |deadline| deadline.min(poll)))
+        } else {
+            deadline
+        };
+        match (wait, deadline) {
+            (ControlFlow::Wait, Some(deadline)) => ControlFlow::WaitUntil(deadline),
+            (ControlFlow::WaitUntil(t), Some(deadline)) if t > deadline => ControlFlow::WaitUntil(deadline),
+            (wait, _) => wait,
         }
+    }
 
-        None
+    // Returns the inner size of the window in logical pixels.
+    fn logical_size(&self) -> [f64; 2] {
+        let window = self.get_window();
+        window_size(window.inner_size(), window.scale_factor())
     }
 
     fn fake_capture(&mut self) {
-        if let Some(pos) = self.last_cursor_pos {
+        if let Some(pos) = self.state.last_cursor_pos {
             // Fake capturing of cursor.
-            let size = self.size();
-            let cx = size.width / 2.0;
-            let cy = size.height / 2.0;
+            let window = self.get_window();
+            let [cx, cy] = window_center(window.inner_size(), window.scale_factor());
             let dx = cx - pos[0];
             let dy = cy - pos[1];
             if dx != 0.0 || dy != 0.0 {
-                if let Ok(_) = self.window.set_cursor_position(winit::dpi::PhysicalPosition{x: cx, y: cy}) {
-                    self.last_cursor_pos = Some([cx, cy]);
+                if let Ok(_) = self.get_window().set_cursor_position(LogicalPosition{x: cx, y: cy}) {
+                    self.state.last_cursor_pos = Some([cx, cy]);
                 }
             }
         }
@@ -347,7 +1169,7 @@ impl WinitWindow {
 
 }
 
-impl Window for WinitWindow {
+impl<T: 'static + Send + Sync> Window for WinitWindow<T> {
     fn set_should_close(&mut self, value: bool) {
         self.should_close = value;
     }
@@ -357,122 +1179,79 @@ impl Window for WinitWindow {
     }
 
     fn size(&self) -> Size {
-        let (w, h) : (i32, i32) = self.get_window().inner_size().into();
-        let hidpi = self.get_window().scale_factor();
-        Size{width: (w as f64 / hidpi), height: (h as f64 / hidpi)}
+        let size = self.logical_size();
+        Size{width: size[0], height: size[1]}
     }
 
     fn swap_buffers(&mut self) {
-        /*
-        // This window backend was made for use with a vulkan renderer that handles swapping by
-        //  itself, if you need it here open up an issue. What we can use this for however is
-        //  detecting the end of a frame, which we can use to gather up cursor_accumulator data.
-
-        if self.capture_cursor {
-            let mut center = self.get_window().inner_size();
-            center.width /= 2;
-            center.height /= 2;
-
-            // Center-lock the cursor if we're using capture_cursor
-            self.get_window().set_cursor_position(LogicalPosition{x: center.width as i32, y: center.height as i32}).unwrap();
-
-            // Create a relative input based on the distance from the center
-            self.queued_events.push_back(Event::Input(
-                Input::Move(Motion::MouseRelative([
-                    self.cursor_accumulator.x,
-                    self.cursor_accumulator.y,
-                ])
-            ), None));
-
-            self.cursor_accumulator = LogicalPosition::new(0.0, 0.0);
+        // Without an OpenGL context or framebuffer, presenting is left to the renderer.
+        #[cfg(feature="use-opengl")]
+        {
+            if let Some(ref ctx) = self.ctx {
+                if let Err(err) = ctx.swap_buffers() {
+                    warn!("failed to swap buffers: {}", err);
+                }
+            }
+        }
+        #[cfg(feature="use-software")]
+        {
+            if let Some(ref mut framebuffer) = self.framebuffer {
+                framebuffer.present();
+            }
         }
-        */
     }
 
     fn wait_event(&mut self) -> Event {
-        self.poll_event().unwrap()
-    }
-
-    fn wait_event_timeout(&mut self, _timeout: Duration) -> Option<Event> {
-        self.poll_event()
-    }
-
-    fn poll_event(&mut self) -> Option<Event> {
-        /*
-        let mut center : LogicalSize<f64> = self.get_window().inner_size().to_logical(self.get_window().scale_factor());
-        center.width /= 2.;
-        center.height /= 2.;
-
-        // Add all events we got to the event queue, since winit only allows us to get all pending
-        //  events at once.
-        {
-            //let mut events: Vec<winit::event::Event<()>> = Vec::new();
-            for event in self.events.into_iter() {
-                self.handle_event(event, center)
+        loop {
+            if let Some(event) = self.poll_event() {
+                return event;
             }
-            
+            if !self.can_pump() {
+                panic!("`wait_event` requires the window to own its event loop on a desktop platform");
+            }
+            self.pump_events(ControlFlow::Wait);
         }
+    }
 
-        // Get the first event in the queue
-        let event = self.queued_events.pop_front();
-
-        // Check if we got a close event, if we did we need to mark ourselves as should-close
-        if let &Some(Event::Input(Input::Close(_), ..)) = &event {
-            self.set_should_close(true);
+    fn wait_event_timeout(&mut self, timeout: Duration) -> Option<Event> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.poll_event() {
+                return Some(event);
+            }
+            if !self.can_pump() || Instant::now() >= deadline {
+                return None;
+            }
+            self.pump_events(ControlFlow::WaitUntil(deadline));
         }
+    }
 
-        event
-        */
-        use winit::event::Event as E;
-        use winit::event::WindowEvent as WE;
+    fn poll_event(&mut self) -> Option<Event> {
+        // The event loop is pumped at most once per call,
+        // such that a stream of unknown events can not block.
+        let mut pumped = false;
 
-        // Loop to skip unknown events.
         loop {
-            let event = self.pre_pop_front_event();
-            if event.is_some() {
-                return event.map(|x| Event::Input(x, None));
+            let scale_factor = self.get_window().scale_factor();
+            if let Some(event) = self.state.poll(&mut self.events, scale_factor) {
+                if let Event::Input(ref input, _) = event {
+                    self.apply_input(input);
+                }
+                return Some(event);
             }
 
-            if self.events.len() == 0 {
-                /*
-                let ref mut events = self.events;
-                self.events_loop.run_return(move |ev, _, flow| {
-                    println!("{:?}", ev);
-                    match ev {
-                        winit::event::Event::LoopDestroyed => {
-                        },
-                        _ => {
-                            events.push_back(ev.to_static().unwrap()); 
-                            *flow = winit::event_loop::ControlFlow::Exit;
-                        }
-                    }
-                });
-                */
-                //println!("No events: {}", self.events.len());
-                return None;
-            }
-            let mut ev = self.events.pop_front();
-
-            if self.is_capturing_cursor &&
-               self.last_cursor_pos.is_none() {
-                if let Some(E::WindowEvent {
-                    event: WE::CursorMoved{ position, ..}, ..
-                }) = ev {
-                    // Ignore this event since mouse positions
-                    // should not be emitted when capturing cursor.
-                    self.last_cursor_pos = Some([position.x as f64, position.y as f64]);
-
-                    if self.events.len() == 0 {
-                        return None;    
-                    }
-                    ev = self.events.pop_front();
+            #[cfg(feature="gamepad")]
+            {
+                if let Some(input) = self.gamepads.as_mut().and_then(|gamepads| gamepads.poll()) {
+                    return Some(Event::Input(input, None));
                 }
             }
 
-            let mut unknown = false;
-            //let event = self.handle_event(ev, &mut unknown);
-            //if unknown {continue};
-            //return event.map(|x| Event::Input(x, None));
+            if pumped {
+                return None;
+            }
+            self.pump_events(ControlFlow::Poll);
+            pumped = true;
         }
     }
 
@@ -482,7 +1261,7 @@ impl Window for WinitWindow {
     }
 }
 
-impl AdvancedWindow for WinitWindow {
+impl<T: 'static + Send + Sync> AdvancedWindow for WinitWindow<T> {
     fn get_title(&self) -> String {
         self.title.clone()
     }
@@ -501,23 +1280,18 @@ impl AdvancedWindow for WinitWindow {
     }
 
     fn set_capture_cursor(&mut self, value: bool) {
-        // Normally we would call `.grab_cursor(true)`
-        // but since relative mouse events does not work,
-        // the capturing of cursor is faked by hiding the cursor
-        // and setting the position to the center of window.
-        self.is_capturing_cursor = value;
-        self.window.set_cursor_visible(!value);
-        if value {
-            self.fake_capture();
+        let mode = if value { CursorMode::Locked } else { CursorMode::Normal };
+        if let Err(err) = self.set_cursor_mode(mode) {
+            warn!("failed to set cursor mode: {}", err);
         }
     }
 
     fn get_automatic_close(&self) -> bool {
-        false
+        self.automatic_close
     }
 
-    fn set_automatic_close(&mut self, _value: bool) {
-        // TODO: Implement this
+    fn set_automatic_close(&mut self, value: bool) {
+        self.automatic_close = value;
     }
 
     fn show(&mut self) {
@@ -529,52 +1303,70 @@ impl AdvancedWindow for WinitWindow {
     }
 
     fn get_position(&self) -> Option<Position> {
-        match self.get_window().outer_position() {
-            Ok(pos) => Some(Position { x: pos.x as i32, y: pos.y as i32 }),
+        let window = self.get_window();
+        match window.outer_position() {
+            Ok(pos) => Some(window_position(pos, window.scale_factor())),
             Err(_) => None
         }
     }
 
     fn set_position<P: Into<Position>>(&mut self, val: P) {
         let pos: Position = val.into();
-        self.get_window().set_outer_position(LogicalPosition{x: pos.x, y: pos.y});
+        let window = self.get_window();
+        window.set_outer_position(physical_position(pos, window.scale_factor()));
     }
 
     fn set_size<S: Into<Size>>(&mut self, size: S) {
         let size: Size = size.into();
-        let hidpi = self.get_window().scale_factor();
-        self.get_window().set_inner_size(LogicalSize::new(
-            size.width as f64 * hidpi,
-            size.height as f64 * hidpi
-        ));
+        let window = self.get_window();
+        window.set_inner_size(physical_size(size, window.scale_factor()));
     }
 }
 
-impl BuildFromWindowSettings for WinitWindow {
+// Vulkan windows need an instance, see `WinitWindow::new_vulkano`.
+#[cfg(not(feature="use-vulkano"))]
+impl<T: 'static + Send + Sync> BuildFromWindowSettings for WinitWindow<T> {
     fn build_from_window_settings(settings: &WindowSettings) -> Result<Self, Box<Error>> {
-        Ok(WinitWindow::new(settings))
+        Ok(WinitWindow::try_with_user_event(settings)?)
     }
 }
-/*
-impl OpenGLWindow for WinitWindow {
+
+// The native handles include the display connection (X11/Wayland),
+// so renderers can create surfaces from the window handle alone.
+unsafe impl<T: 'static> HasRawWindowHandle for WinitWindow<T> {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.get_window().raw_window_handle()
+    }
+}
+
+#[cfg(feature="use-opengl")]
+impl<T: 'static + Send + Sync> OpenGLWindow for WinitWindow<T> {
     fn get_proc_address(&mut self, proc_name: &str) -> ProcAddress {
-        //self.ctx.get_proc_address(proc_name) as *const _
-        std::ptr::null()
+        match self.ctx {
+            Some(ref ctx) => ctx.get_proc_address(proc_name) as *const _,
+            None => std::ptr::null(),
+        }
     }
 
     fn is_current(&self) -> bool {
-        //self.ctx.is_current()
-        true
+        match self.ctx {
+            Some(ref ctx) => ctx.is_current(),
+            None => false,
+        }
     }
 
     fn make_current(&mut self) {
-        //use std::mem::{replace, zeroed, forget};
-
-        //let ctx = replace(&mut self.ctx, unsafe{zeroed()});
-        //forget(replace(&mut self.ctx, unsafe {ctx.make_current().unwrap()}));
+        if let Some(ctx) = self.ctx.take() {
+            self.ctx = Some(match unsafe { ctx.make_current() } {
+                Ok(ctx) => ctx,
+                Err((ctx, err)) => {
+                    warn!("failed to make context current: {}", err);
+                    ctx
+                }
+            });
+        }
     }
 }
-*/
 
 /// Maps Glutin's key to Piston's key.
 pub fn map_key(keycode: winit::event::VirtualKeyCode) -> keyboard::Key {
@@ -619,10 +1411,10 @@ pub fn map_key(keycode: winit::event::VirtualKeyCode) -> keyboard::Key {
         K::X => Key::X,
         K::Y => Key::Y,
         K::Z => Key::Z,
-        K::Apostrophe => Key::Unknown,
+        K::Apostrophe => Key::Quote,
         K::Backslash => Key::Backslash,
         K::Back => Key::Backspace,
-        // K::CapsLock => Key::CapsLock,
+        K::Capital => Key::CapsLock,
         K::Delete => Key::Delete,
         K::Comma => Key::Comma,
         K::Down => Key::Down,
@@ -666,7 +1458,8 @@ pub fn map_key(keycode: winit::event::VirtualKeyCode) -> keyboard::Key {
         K::Numpad7 => Key::NumPad7,
         K::Numpad8 => Key::NumPad8,
         K::Numpad9 => Key::NumPad9,
-        K::NumpadComma => Key::NumPadDecimal,
+        K::NumpadComma => Key::NumPadComma,
+        K::Decimal => Key::NumPadPeriod,
         K::Divide => Key::NumPadDivide,
         K::Multiply => Key::NumPadMultiply,
         K::Subtract => Key::NumPadMinus,
@@ -679,13 +1472,14 @@ pub fn map_key(keycode: winit::event::VirtualKeyCode) -> keyboard::Key {
         K::RShift => Key::RShift,
         K::RControl => Key::RCtrl,
         K::RAlt => Key::RAlt,
-        // Map to backslash?
-        // K::GraveAccent => Key::Unknown,
+        K::LWin => Key::LGui,
+        K::RWin => Key::RGui,
+        K::Grave => Key::Backquote,
         K::Home => Key::Home,
         K::Insert => Key::Insert,
         K::Left => Key::Left,
         K::LBracket => Key::LeftBracket,
-        // K::Menu => Key::Menu,
+        K::Apps => Key::Menu,
         K::Minus => Key::Minus,
         K::Numlock => Key::NumLockClear,
         K::PageDown => Key::PageDown,
@@ -701,15 +1495,264 @@ pub fn map_key(keycode: winit::event::VirtualKeyCode) -> keyboard::Key {
         K::Space => Key::Space,
         K::Tab => Key::Tab,
         K::Up => Key::Up,
-        // K::World1 => Key::Unknown,
-        // K::World2 => Key::Unknown,
-        _ => Key::Unknown,
+        K::At => Key::At,
+        K::Caret => Key::Caret,
+        K::Colon => Key::Colon,
+        K::Underline => Key::Underscore,
+        K::Sysrq => Key::Sysreq,
+        K::Copy => Key::Copy,
+        K::Cut => Key::Cut,
+        K::Paste => Key::Paste,
+        K::Stop => Key::Stop,
+        K::Power => Key::Power,
+        K::Sleep => Key::Sleep,
+        K::Mute => Key::Mute,
+        K::VolumeDown => Key::VolumeDown,
+        K::VolumeUp => Key::VolumeUp,
+        K::PlayPause => Key::AudioPlay,
+        K::MediaStop => Key::AudioStop,
+        K::NextTrack => Key::AudioNext,
+        K::PrevTrack => Key::AudioPrev,
+        K::MediaSelect => Key::MediaSelect,
+        K::Mail => Key::Mail,
+        K::Calculator => Key::Calculator,
+        K::MyComputer => Key::Computer,
+        K::WebBack => Key::AcBack,
+        K::WebForward => Key::AcForward,
+        K::NavigateBackward => Key::AcBack,
+        K::NavigateForward => Key::AcForward,
+        K::WebHome => Key::AcHome,
+        K::WebRefresh => Key::AcRefresh,
+        K::WebSearch => Key::AcSearch,
+        K::WebStop => Key::AcStop,
+        K::WebFavorites => Key::AcBookmarks,
+        // No Piston equivalent.
+        K::AbntC1 | K::AbntC2 | K::Ax | K::Compose | K::Convert | K::Kana | K::Kanji |
+        K::NoConvert | K::OEM102 | K::Unlabeled | K::Wake | K::Yen => Key::Unknown,
     };
-    println!("Keycode press: {:?} {:?}", keycode, key);
+    trace!("mapped key: winit_key={:?} piston_key={:?}", keycode, key);
     key
 }
 
+/// Maps Piston's key to winit's key.
+///
+/// This is the inverse of `map_key`.
+/// Returns `None` for keys that winit has no key code for.
+/// Where several winit keys map to the same Piston key,
+/// the most common one is returned, e.g. `WebBack` for `AcBack`.
+pub fn unmap_key(key: keyboard::Key) -> Option<winit::event::VirtualKeyCode> {
+    use input::keyboard::Key;
+    use winit::event::VirtualKeyCode as K;
+
+    Some(match key {
+        Key::D0 => K::Key0,
+        Key::D1 => K::Key1,
+        Key::D2 => K::Key2,
+        Key::D3 => K::Key3,
+        Key::D4 => K::Key4,
+        Key::D5 => K::Key5,
+        Key::D6 => K::Key6,
+        Key::D7 => K::Key7,
+        Key::D8 => K::Key8,
+        Key::D9 => K::Key9,
+        Key::A => K::A,
+        Key::B => K::B,
+        Key::C => K::C,
+        Key::D => K::D,
+        Key::E => K::E,
+        Key::F => K::F,
+        Key::G => K::G,
+        Key::H => K::H,
+        Key::I => K::I,
+        Key::J => K::J,
+        Key::K => K::K,
+        Key::L => K::L,
+        Key::M => K::M,
+        Key::N => K::N,
+        Key::O => K::O,
+        Key::P => K::P,
+        Key::Q => K::Q,
+        Key::R => K::R,
+        Key::S => K::S,
+        Key::T => K::T,
+        Key::U => K::U,
+        Key::V => K::V,
+        Key::W => K::W,
+        Key::X => K::X,
+        Key::Y => K::Y,
+        Key::Z => K::Z,
+        Key::Quote => K::Apostrophe,
+        Key::Backslash => K::Backslash,
+        Key::Backspace => K::Back,
+        Key::CapsLock => K::Capital,
+        Key::Delete => K::Delete,
+        Key::Comma => K::Comma,
+        Key::Down => K::Down,
+        Key::End => K::End,
+        Key::Return => K::Return,
+        Key::Equals => K::Equals,
+        Key::Escape => K::Escape,
+        Key::F1 => K::F1,
+        Key::F2 => K::F2,
+        Key::F3 => K::F3,
+        Key::F4 => K::F4,
+        Key::F5 => K::F5,
+        Key::F6 => K::F6,
+        Key::F7 => K::F7,
+        Key::F8 => K::F8,
+        Key::F9 => K::F9,
+        Key::F10 => K::F10,
+        Key::F11 => K::F11,
+        Key::F12 => K::F12,
+        Key::F13 => K::F13,
+        Key::F14 => K::F14,
+        Key::F15 => K::F15,
+        Key::F16 => K::F16,
+        Key::F17 => K::F17,
+        Key::F18 => K::F18,
+        Key::F19 => K::F19,
+        Key::F20 => K::F20,
+        Key::F21 => K::F21,
+        Key::F22 => K::F22,
+        Key::F23 => K::F23,
+        Key::F24 => K::F24,
+        Key::NumPad0 => K::Numpad0,
+        Key::NumPad1 => K::Numpad1,
+        Key::NumPad2 => K::Numpad2,
+        Key::NumPad3 => K::Numpad3,
+        Key::NumPad4 => K::Numpad4,
+        Key::NumPad5 => K::Numpad5,
+        Key::NumPad6 => K::Numpad6,
+        Key::NumPad7 => K::Numpad7,
+        Key::NumPad8 => K::Numpad8,
+        Key::NumPad9 => K::Numpad9,
+        Key::NumPadComma => K::NumpadComma,
+        Key::NumPadPeriod => K::Decimal,
+        Key::NumPadDivide => K::Divide,
+        Key::NumPadMultiply => K::Multiply,
+        Key::NumPadMinus => K::Subtract,
+        Key::NumPadPlus => K::Add,
+        Key::NumPadEnter => K::NumpadEnter,
+        Key::NumPadEquals => K::NumpadEquals,
+        Key::LShift => K::LShift,
+        Key::LCtrl => K::LControl,
+        Key::LAlt => K::LAlt,
+        Key::RShift => K::RShift,
+        Key::RCtrl => K::RControl,
+        Key::RAlt => K::RAlt,
+        Key::LGui => K::LWin,
+        Key::RGui => K::RWin,
+        Key::Backquote => K::Grave,
+        Key::Home => K::Home,
+        Key::Insert => K::Insert,
+        Key::Left => K::Left,
+        Key::LeftBracket => K::LBracket,
+        Key::Menu => K::Apps,
+        Key::Minus => K::Minus,
+        Key::NumLockClear => K::Numlock,
+        Key::PageDown => K::PageDown,
+        Key::PageUp => K::PageUp,
+        Key::Pause => K::Pause,
+        Key::Period => K::Period,
+        Key::PrintScreen => K::Snapshot,
+        Key::Right => K::Right,
+        Key::RightBracket => K::RBracket,
+        Key::ScrollLock => K::Scroll,
+        Key::Semicolon => K::Semicolon,
+        Key::Slash => K::Slash,
+        Key::Space => K::Space,
+        Key::Tab => K::Tab,
+        Key::Up => K::Up,
+        Key::At => K::At,
+        Key::Caret => K::Caret,
+        Key::Colon => K::Colon,
+        Key::Underscore => K::Underline,
+        Key::Sysreq => K::Sysrq,
+        Key::Copy => K::Copy,
+        Key::Cut => K::Cut,
+        Key::Paste => K::Paste,
+        Key::Stop => K::Stop,
+        Key::Power => K::Power,
+        Key::Sleep => K::Sleep,
+        Key::Mute => K::Mute,
+        Key::VolumeDown => K::VolumeDown,
+        Key::VolumeUp => K::VolumeUp,
+        Key::AudioPlay => K::PlayPause,
+        Key::AudioStop => K::MediaStop,
+        Key::AudioNext => K::NextTrack,
+        Key::AudioPrev => K::PrevTrack,
+        Key::MediaSelect => K::MediaSelect,
+        Key::Mail => K::Mail,
+        Key::Calculator => K::Calculator,
+        Key::Computer => K::MyComputer,
+        Key::AcBack => K::WebBack,
+        Key::AcForward => K::WebForward,
+        Key::AcHome => K::WebHome,
+        Key::AcRefresh => K::WebRefresh,
+        Key::AcSearch => K::WebSearch,
+        Key::AcStop => K::WebStop,
+        Key::AcBookmarks => K::WebFavorites,
+        _ => return None,
+    })
+}
+
+/// Maps winit's modifier state to Piston's modifier keys.
+pub fn map_modifiers(modifiers: winit::event::ModifiersState) -> keyboard::ModifierKey {
+    use input::keyboard::ModifierKey;
+
+    let mut keys = ModifierKey::NO_MODIFIER;
+    if modifiers.ctrl() {
+        keys.insert(ModifierKey::CTRL);
+    }
+    if modifiers.shift() {
+        keys.insert(ModifierKey::SHIFT);
+    }
+    if modifiers.alt() {
+        keys.insert(ModifierKey::ALT);
+    }
+    if modifiers.logo() {
+        keys.insert(ModifierKey::GUI);
+    }
+    keys
+}
+
+// The Piston mouse buttons of winit's `MouseButton::Other` codes.
+// X11 reports back and forward as buttons 8 and 9, after the scroll buttons,
+// and Wayland reports no other buttons.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+const OTHER_MOUSE_BUTTONS: &[(u8, MouseButton)] = &[
+    (8, MouseButton::X1),
+    (9, MouseButton::X2),
+    (10, MouseButton::Button6),
+    (11, MouseButton::Button7),
+    (12, MouseButton::Button8),
+];
+
+// Windows reports the X buttons by their index.
+#[cfg(target_os = "windows")]
+const OTHER_MOUSE_BUTTONS: &[(u8, MouseButton)] = &[
+    (1, MouseButton::X1),
+    (2, MouseButton::X2),
+];
+
+// The web reports buttons after the first three from 0.
+#[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+              target_os = "netbsd", target_os = "openbsd", target_os = "windows")))]
+const OTHER_MOUSE_BUTTONS: &[(u8, MouseButton)] = &[
+    (0, MouseButton::X1),
+    (1, MouseButton::X2),
+    (2, MouseButton::Button6),
+    (3, MouseButton::Button7),
+    (4, MouseButton::Button8),
+];
+
 /// Maps Glutin's mouse button to Piston's mouse button.
+///
+/// `MouseButton::Other` codes depend on the platform,
+/// such that back and forward buttons map to `X1` and `X2`.
+/// Codes without a Piston equivalent map to `Unknown`,
+/// see `WinitWindow::set_mouse_override` to map them.
 pub fn map_mouse(mouse_button: winit::event::MouseButton) -> MouseButton {
     use winit::event::MouseButton as M;
 
@@ -717,11 +1760,208 @@ pub fn map_mouse(mouse_button: winit::event::MouseButton) -> MouseButton {
         M::Left => MouseButton::Left,
         M::Right => MouseButton::Right,
         M::Middle => MouseButton::Middle,
-        M::Other(0) => MouseButton::X1,
-        M::Other(1) => MouseButton::X2,
-        M::Other(2) => MouseButton::Button6,
-        M::Other(3) => MouseButton::Button7,
-        M::Other(4) => MouseButton::Button8,
-        _ => MouseButton::Unknown
+        M::Other(code) => OTHER_MOUSE_BUTTONS.iter()
+            .find(|&&(c, _)| c == code)
+            .map(|&(_, button)| button)
+            .unwrap_or(MouseButton::Unknown),
+    }
+}
+
+/// Maps Piston's mouse button to winit's mouse button.
+///
+/// This is the inverse of `map_mouse`.
+pub fn unmap_mouse(mouse_button: MouseButton) -> Option<winit::event::MouseButton> {
+    use winit::event::MouseButton as M;
+
+    match mouse_button {
+        MouseButton::Left => Some(M::Left),
+        MouseButton::Right => Some(M::Right),
+        MouseButton::Middle => Some(M::Middle),
+        MouseButton::Unknown => None,
+        button => OTHER_MOUSE_BUTTONS.iter()
+            .find(|&&(_, b)| b == button)
+            .map(|&(code, _)| M::Other(code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Creates a window, which requires a display.
+    // Tests run on worker threads, so the event loop is created with `new_any_thread`.
+    #[cfg(all(target_os = "linux", not(feature="use-vulkano")))]
+    fn test_window() -> WinitWindow {
+        use winit::platform::unix::EventLoopExtUnix;
+
+        assert!(has_display(), "this test requires a display");
+        let settings = WindowSettings::new("test", (64, 64));
+        let events_loop: EventLoop<()> = EventLoop::new_any_thread();
+        let window = window_builder(&settings, &events_loop).build(&events_loop).unwrap();
+        let mut window = WinitWindow::new_with_window(&settings, window);
+        window.events_loop = Some(events_loop);
+        window
+    }
+
+    // Every winit key code.
+    const VIRTUAL_KEY_CODES: &[winit::event::VirtualKeyCode] = {
+        use winit::event::VirtualKeyCode as K;
+        &[
+            K::Key1, K::Key2, K::Key3, K::Key4, K::Key5, K::Key6, K::Key7, K::Key8, K::Key9,
+            K::Key0, K::A, K::B, K::C, K::D, K::E, K::F, K::G, K::H, K::I, K::J, K::K, K::L, K::M,
+            K::N, K::O, K::P, K::Q, K::R, K::S, K::T, K::U, K::V, K::W, K::X, K::Y, K::Z, K::Escape,
+            K::F1, K::F2, K::F3, K::F4, K::F5, K::F6, K::F7, K::F8, K::F9, K::F10, K::F11, K::F12,
+            K::F13, K::F14, K::F15, K::F16, K::F17, K::F18, K::F19, K::F20, K::F21, K::F22, K::F23,
+            K::F24, K::Snapshot, K::Scroll, K::Pause, K::Insert, K::Home, K::Delete, K::End,
+            K::PageDown, K::PageUp, K::Left, K::Up, K::Right, K::Down, K::Back, K::Return, K::Space,
+            K::Compose, K::Caret, K::Numlock, K::Numpad0, K::Numpad1, K::Numpad2, K::Numpad3,
+            K::Numpad4, K::Numpad5, K::Numpad6, K::Numpad7, K::Numpad8, K::Numpad9, K::AbntC1,
+            K::AbntC2, K::Add, K::Apostrophe, K::Apps, K::At, K::Ax, K::Backslash, K::Calculator,
+            K::Capital, K::Colon, K::Comma, K::Convert, K::Decimal, K::Divide, K::Equals, K::Grave,
+            K::Kana, K::Kanji, K::LAlt, K::LBracket, K::LControl, K::LShift, K::LWin, K::Mail,
+            K::MediaSelect, K::MediaStop, K::Minus, K::Multiply, K::Mute, K::MyComputer,
+            K::NavigateForward, K::NavigateBackward, K::NextTrack, K::NoConvert, K::NumpadComma,
+            K::NumpadEnter, K::NumpadEquals, K::OEM102, K::Period, K::PlayPause, K::Power,
+            K::PrevTrack, K::RAlt, K::RBracket, K::RControl, K::RShift, K::RWin, K::Semicolon,
+            K::Slash, K::Sleep, K::Stop, K::Subtract, K::Sysrq, K::Tab, K::Underline, K::Unlabeled,
+            K::VolumeDown, K::VolumeUp, K::Wake, K::WebBack, K::WebFavorites, K::WebForward,
+            K::WebHome, K::WebRefresh, K::WebSearch, K::WebStop, K::Yen, K::Copy, K::Paste, K::Cut,
+        ]
+    };
+
+    #[test]
+    fn map_key_only_unknown_without_equivalent() {
+        use winit::event::VirtualKeyCode as K;
+
+        let no_equivalent = [
+            K::AbntC1, K::AbntC2, K::Ax, K::Compose, K::Convert, K::Kana, K::Kanji,
+            K::NoConvert, K::OEM102, K::Unlabeled, K::Wake, K::Yen,
+        ];
+        for &code in VIRTUAL_KEY_CODES {
+            assert_eq!(map_key(code) == Key::Unknown, no_equivalent.contains(&code), "{:?}", code);
+        }
+    }
+
+    // Every Piston key, by the ranges of its key codes.
+    fn piston_keys() -> Vec<Key> {
+        (0..0x80).chain(0x4000_0000..0x4000_0200)
+            .map(Key::from)
+            .filter(|&key| key != Key::Unknown)
+            .collect()
+    }
+
+    #[test]
+    fn unmap_key_round_trip() {
+        for key in piston_keys() {
+            if let Some(code) = unmap_key(key) {
+                assert_eq!(map_key(code), key, "{:?}", code);
+            }
+        }
+        for &code in VIRTUAL_KEY_CODES {
+            let key = map_key(code);
+            if key != Key::Unknown {
+                assert!(unmap_key(key).is_some(), "{:?}", key);
+            }
+        }
+    }
+
+    #[test]
+    fn unmap_mouse_round_trip() {
+        let buttons = [
+            MouseButton::Unknown, MouseButton::Left, MouseButton::Right, MouseButton::Middle,
+            MouseButton::X1, MouseButton::X2, MouseButton::Button6, MouseButton::Button7,
+            MouseButton::Button8,
+        ];
+        for &button in &buttons {
+            if let Some(winit_button) = unmap_mouse(button) {
+                assert_eq!(map_mouse(winit_button), button);
+            }
+        }
+        for code in 0..=255 {
+            let button = map_mouse(WinitMouseButton::Other(code));
+            if button != MouseButton::Unknown {
+                assert_eq!(unmap_mouse(button), Some(WinitMouseButton::Other(code)));
+            }
+        }
+    }
+
+    #[test]
+    fn text_filter() {
+        assert!(TextFilter::Sdl.accepts('a'));
+        assert!(!TextFilter::Sdl.accepts('\u{8}'));
+        assert!(TextFilter::PassThrough.accepts('\u{8}'));
+        assert!(!TextFilter::Custom(char::is_alphabetic).accepts('1'));
+    }
+
+    #[test]
+    fn scroll_policy() {
+        let lines = ScrollDelta::Lines([1.0, -2.0]);
+        let pixels = ScrollDelta::Pixels([40.0, 20.0]);
+        let policy = ScrollPolicy::default();
+        assert_eq!(policy.apply(lines), [1.0, -2.0]);
+        assert_eq!(policy.apply(pixels), [2.0, 1.0]);
+
+        let policy = ScrollPolicy { lines_per_pixel: 0.25, ..ScrollPolicy::default() };
+        assert_eq!(policy.apply(lines), [1.0, -2.0]);
+        assert_eq!(policy.apply(pixels), [10.0, 5.0]);
+
+        let policy = ScrollPolicy { lines_per_pixel: 0.25, invert: true, scale: [2.0, 0.5] };
+        assert_eq!(policy.apply(lines), [-2.0, 1.0]);
+        assert_eq!(policy.apply(pixels), [-20.0, -2.5]);
+    }
+
+    const SCALE_FACTORS: [f64; 3] = [1.0, 1.5, 2.0];
+
+    #[test]
+    fn window_size_is_logical() {
+        for &scale_factor in &SCALE_FACTORS {
+            // As set by `set_size`.
+            let size = physical_size(Size { width: 640.0, height: 480.0 }, scale_factor);
+            assert_eq!(size, PhysicalSize::new((640.0 * scale_factor) as u32, (480.0 * scale_factor) as u32));
+            assert_eq!(window_size(size, scale_factor), [640.0, 480.0]);
+        }
+    }
+
+    #[test]
+    fn window_position_is_logical() {
+        for &scale_factor in &SCALE_FACTORS {
+            // As set by `set_position`.
+            let pos = physical_position(Position { x: 100, y: 50 }, scale_factor);
+            assert_eq!(pos, PhysicalPosition::new((100.0 * scale_factor) as i32, (50.0 * scale_factor) as i32));
+            assert_eq!(window_position(pos, scale_factor), Position { x: 100, y: 50 });
+        }
+    }
+
+    #[test]
+    fn window_center_is_logical() {
+        for &scale_factor in &SCALE_FACTORS {
+            let size = PhysicalSize::new(1200, 900);
+            let center = window_center(size, scale_factor);
+            assert_eq!(center, [600.0 / scale_factor, 450.0 / scale_factor]);
+            // The cursor is reported at the center after warping.
+            let pos: PhysicalPosition<f64> = LogicalPosition::new(center[0], center[1]).to_physical(scale_factor);
+            assert_eq!(pos, PhysicalPosition::new(600.0, 450.0));
+        }
+    }
+
+    #[test]
+    fn cursor_and_motion_are_logical() {
+        for &scale_factor in &SCALE_FACTORS {
+            let pos = PhysicalPosition::new(300.0, 150.0);
+            assert_eq!(cursor_position(pos, scale_factor), [300.0 / scale_factor, 150.0 / scale_factor]);
+            assert_eq!(motion_delta((6.0, -3.0), scale_factor), [6.0 / scale_factor, -3.0 / scale_factor]);
+        }
+    }
+
+    #[test]
+    #[ignore] // Requires a display.
+    #[cfg(all(target_os = "linux", not(feature="use-vulkano")))]
+    fn raw_window_handle_after_construction() {
+        let window = test_window();
+        match window.raw_window_handle() {
+            RawWindowHandle::Xlib(handle) => assert!(handle.window != 0),
+            RawWindowHandle::Wayland(handle) => assert!(!handle.surface.is_null()),
+            handle => panic!("unexpected window handle {:?}", handle),
+        }
     }
 }
@@ -0,0 +1,267 @@
+//! OpenGL context creation, enabled by the `use-opengl` feature.
+//!
+//! The context is created with glutin as a raw context on top of the winit window,
+//! using GLX or EGL on Unix and WGL on Windows.
+//! On X11 the window is created with the visual of the context, see `X11Visual`.
+//! Mesa's software rasterizer can be selected with `LIBGL_ALWAYS_SOFTWARE=1`.
+
+use std::ffi::c_void;
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+use std::os::raw::c_int;
+
+use glutin::{
+    ContextBuilder,
+    CreationError,
+    GlProfile,
+    GlRequest,
+    NotCurrent,
+    PossiblyCurrent,
+    RawContext,
+};
+use window::{Api, WindowSettings};
+use winit::window::Window as OriginalWinitWindow;
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+use winit::{
+    event_loop::EventLoop,
+    platform::unix::{EventLoopWindowTargetExtUnix, WindowBuilderExtUnix},
+    window::WindowBuilder,
+};
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+use x11_dl::{
+    glx::{self, Glx},
+    xlib::{self, Xlib},
+};
+
+/// An OpenGL context that is current on this thread.
+pub type Context = RawContext<PossiblyCurrent>;
+
+/// Creates an OpenGL context for the window and makes it current.
+///
+/// Returns `None` when the settings request a graphics API other than OpenGL.
+/// When no graphics API is requested, OpenGL 3.2 is used.
+pub fn create_context(
+    window: &OriginalWinitWindow,
+    settings: &WindowSettings
+) -> Result<Option<Context>, CreationError> {
+    let version = match gl_version(settings) {
+        Some(version) => version,
+        None => return Ok(None),
+    };
+
+    let mut builder = ContextBuilder::new()
+        .with_gl(GlRequest::Specific(glutin::Api::OpenGl, version))
+        .with_vsync(settings.get_vsync())
+        .with_srgb(settings.get_srgb());
+    if version >= (3, 2) {
+        builder = builder.with_gl_profile(GlProfile::Core);
+    }
+    let samples = settings.get_samples();
+    if samples != 0 {
+        builder = builder.with_multisampling(samples as u16);
+    }
+
+    let ctx = unsafe { build_raw_context(builder, window)? };
+    match unsafe { ctx.make_current() } {
+        Ok(ctx) => Ok(Some(ctx)),
+        Err((_, err)) => Err(CreationError::OsError(format!("{}", err))),
+    }
+}
+
+/// Returns the requested OpenGL version,
+/// or `None` when the settings request a graphics API other than OpenGL.
+fn gl_version(settings: &WindowSettings) -> Option<(u8, u8)> {
+    let api = settings.get_maybe_graphics_api().unwrap_or(Api::opengl(3, 2));
+    if api.api != "OpenGL" {
+        return None;
+    }
+    Some((api.major as u8, api.minor as u8))
+}
+
+/// Loads the OpenGL functions of a current context.
+pub fn load_gl(ctx: &Context) -> glow::Context {
+    unsafe {
+        glow::Context::from_loader_function(|name| ctx.get_proc_address(name) as *const c_void)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+unsafe fn build_raw_context(
+    builder: ContextBuilder<NotCurrent>,
+    window: &OriginalWinitWindow
+) -> Result<RawContext<NotCurrent>, CreationError> {
+    use std::sync::Arc;
+    use glutin::platform::unix::RawContextExt;
+    use glutin::platform::unix::x11::XConnection;
+    use winit::platform::unix::WindowExtUnix;
+
+    if let (Some(display), Some(surface)) = (window.wayland_display(), window.wayland_surface()) {
+        let size = window.inner_size();
+        return builder.build_raw_wayland_context(display as *const _, surface, size.width, size.height);
+    }
+    if let Some(xwin) = window.xlib_window() {
+        // Window ids are global to the X server,
+        // so a separate connection can be used for the context.
+        // Glutin chooses a configuration with the visual of the window.
+        let xconn = XConnection::new(None)
+            .map_err(|err| CreationError::OsError(format!("{:?}", err)))?;
+        return builder.build_raw_x11_context(Arc::new(xconn), xwin);
+    }
+    Err(CreationError::NotSupported("Unknown window system".into()))
+}
+
+/// The X11 visual of a GLX framebuffer configuration matching the window settings.
+///
+/// A context can only be made current on a window with the visual of its configuration,
+/// which for transparent or multisampled windows differs from the default visual.
+/// The window is therefore created with this visual, see `apply`.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+pub struct X11Visual {
+    xlib: Xlib,
+    // The connection the visual was chosen on, which owns the visual.
+    display: *mut xlib::Display,
+    info: *mut xlib::XVisualInfo,
+}
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+impl X11Visual {
+    /// Chooses the visual for an OpenGL window with the settings.
+    ///
+    /// Returns `None` when the event loop uses Wayland, OpenGL is not requested,
+    /// GLX is not available or no configuration matches the settings.
+    /// The window is then created with the default visual.
+    pub fn choose<T: 'static>(settings: &WindowSettings, events_loop: &EventLoop<T>) -> Option<X11Visual> {
+        use std::ptr;
+
+        if events_loop.is_wayland() || gl_version(settings).is_none() {
+            return None;
+        }
+        let xlib = Xlib::open().ok()?;
+        let glx = Glx::open().ok()?;
+        unsafe {
+            let display = (xlib.XOpenDisplay)(ptr::null());
+            if display.is_null() {
+                return None;
+            }
+            let info = choose_visual(&xlib, &glx, display, settings);
+            if info.is_null() {
+                debug!("no GLX configuration matches the window settings, using the default visual");
+                (xlib.XCloseDisplay)(display);
+                return None;
+            }
+            Some(X11Visual { xlib, display, info })
+        }
+    }
+
+    /// Creates the window with this visual.
+    ///
+    /// The visual must outlive building the window.
+    pub fn apply(&self, builder: WindowBuilder) -> WindowBuilder {
+        builder.with_x11_visual(self.info as *const _)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+impl Drop for X11Visual {
+    fn drop(&mut self) {
+        unsafe {
+            (self.xlib.XFree)(self.info as *mut _);
+            (self.xlib.XCloseDisplay)(self.display);
+        }
+    }
+}
+
+// Not defined by x11-dl.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+const GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB: c_int = 0x20b2;
+
+/// Returns the visual of the first framebuffer configuration matching the settings,
+/// or null when none matches.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+unsafe fn choose_visual(
+    xlib: &Xlib,
+    glx: &Glx,
+    display: *mut xlib::Display,
+    settings: &WindowSettings
+) -> *mut xlib::XVisualInfo {
+    use std::ffi::CStr;
+    use std::ptr;
+
+    let screen = (xlib.XDefaultScreen)(display);
+    let transparent = settings.get_transparent();
+    let mut attributes = vec![
+        glx::GLX_X_RENDERABLE, 1,
+        glx::GLX_DRAWABLE_TYPE, glx::GLX_WINDOW_BIT,
+        glx::GLX_RENDER_TYPE, glx::GLX_RGBA_BIT,
+        glx::GLX_X_VISUAL_TYPE, glx::GLX_TRUE_COLOR,
+        glx::GLX_RED_SIZE, 8,
+        glx::GLX_GREEN_SIZE, 8,
+        glx::GLX_BLUE_SIZE, 8,
+        glx::GLX_DOUBLEBUFFER, 1,
+    ];
+    if transparent {
+        attributes.extend(&[glx::GLX_ALPHA_SIZE, 8]);
+    }
+    let samples = settings.get_samples();
+    if samples != 0 {
+        attributes.extend(&[glx::GLX_SAMPLE_BUFFERS, 1, glx::GLX_SAMPLES, samples as c_int]);
+    }
+    if settings.get_srgb() {
+        // Unknown attributes fail the query, so sRGB is only requested when supported.
+        let extensions = (glx.glXQueryExtensionsString)(display, screen);
+        if !extensions.is_null() && CStr::from_ptr(extensions).to_string_lossy()
+            .split_whitespace().any(|ext| ext == "GLX_ARB_framebuffer_sRGB") {
+            attributes.extend(&[GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB, 1]);
+        }
+    }
+    attributes.push(0);
+
+    let mut count = 0;
+    let configs = (glx.glXChooseFBConfig)(display, screen, attributes.as_ptr(), &mut count);
+    if configs.is_null() {
+        return ptr::null_mut();
+    }
+    let mut chosen = ptr::null_mut();
+    for i in 0..count as isize {
+        let info = (glx.glXGetVisualFromFBConfig)(display, *configs.offset(i));
+        if info.is_null() {
+            continue;
+        }
+        // Transparent windows need a visual with an alpha channel.
+        if !transparent || (*info).depth == 32 {
+            chosen = info;
+            break;
+        }
+        (xlib.XFree)(info as *mut _);
+    }
+    (xlib.XFree)(configs as *mut _);
+    chosen
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn build_raw_context(
+    builder: ContextBuilder<NotCurrent>,
+    window: &OriginalWinitWindow
+) -> Result<RawContext<NotCurrent>, CreationError> {
+    use glutin::platform::windows::RawContextExt;
+    use winit::platform::windows::WindowExtWindows;
+
+    builder.build_raw_context(window.hwnd())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+              target_os = "netbsd", target_os = "openbsd", target_os = "windows")))]
+unsafe fn build_raw_context(
+    _builder: ContextBuilder<NotCurrent>,
+    _window: &OriginalWinitWindow
+) -> Result<RawContext<NotCurrent>, CreationError> {
+    Err(CreationError::NotSupported("Raw OpenGL contexts are not supported on this platform".into()))
+}
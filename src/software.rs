@@ -0,0 +1,614 @@
+//! Software presentation of a CPU framebuffer, enabled by the `use-software` feature.
+//!
+//! Pixels are written as RGBA8 and converted to the native BGRX layout when presented,
+//! using X11 shared memory (falling back to `XPutImage`), Wayland shm buffers
+//! or GDI on Windows.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use winit::window::Window as OriginalWinitWindow;
+
+/// An error that occurred while setting up or presenting a framebuffer.
+#[derive(Debug)]
+pub enum FramebufferError {
+    /// Software presentation is not supported by the window system.
+    Unsupported(&'static str),
+    /// Failed to share pixels with the window system.
+    Io(io::Error),
+    /// The window system failed to set up presentation.
+    Os(String),
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FramebufferError::Unsupported(what) =>
+                write!(f, "Software presentation is not supported: {}", what),
+            FramebufferError::Io(ref err) => write!(f, "Failed to present framebuffer: {}", err),
+            FramebufferError::Os(ref err) => write!(f, "Failed to present framebuffer: {}", err),
+        }
+    }
+}
+
+impl Error for FramebufferError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match *self {
+            FramebufferError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FramebufferError {
+    fn from(err: io::Error) -> Self {
+        FramebufferError::Io(err)
+    }
+}
+
+/// A CPU pixel buffer presented to the window by `swap_buffers`.
+///
+/// The buffer is sized to the draw size of the window
+/// and is cleared to transparent black when the window is resized.
+pub struct Framebuffer {
+    pixels: Pixels,
+    presenter: Presenter,
+    // Whether the last present failed, such that repeated failures are logged once.
+    present_failed: bool,
+}
+
+impl Framebuffer {
+    pub(crate) fn new(window: &OriginalWinitWindow) -> Result<Self, FramebufferError> {
+        let size = window.inner_size();
+        Ok(Framebuffer {
+            pixels: Pixels::new(size.width, size.height),
+            presenter: Presenter::new(window)?,
+            present_failed: false,
+        })
+    }
+
+    /// Returns the size of the framebuffer in pixels.
+    pub fn size(&self) -> [u32; 2] {
+        [self.pixels.width, self.pixels.height]
+    }
+
+    /// Returns the RGBA8 pixels, row by row from the top left corner.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels.data
+    }
+
+    /// Returns the RGBA8 pixels for writing, row by row from the top left corner.
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels.data
+    }
+
+    /// Sets the RGBA color of a pixel.
+    ///
+    /// Pixels outside the framebuffer are ignored.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        self.pixels.set_pixel(x, y, color);
+    }
+
+    /// Fills the framebuffer with an RGBA color.
+    pub fn clear(&mut self, color: [u8; 4]) {
+        self.pixels.clear(color);
+    }
+
+    pub(crate) fn resize(&mut self, width: u32, height: u32) {
+        self.pixels.resize(width, height);
+    }
+
+    // Presents the pixels, logging the first of consecutive failures.
+    pub(crate) fn present(&mut self) {
+        let Pixels { width, height, ref data } = self.pixels;
+        if width == 0 || height == 0 {
+            return;
+        }
+        match self.presenter.present(data, width, height) {
+            Ok(()) => self.present_failed = false,
+            Err(err) => {
+                if !self.present_failed {
+                    warn!("{}", err);
+                }
+                self.present_failed = true;
+            }
+        }
+    }
+}
+
+// RGBA8 pixels, row by row from the top left corner.
+struct Pixels {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Pixels {
+    fn new(width: u32, height: u32) -> Pixels {
+        Pixels {
+            width: width,
+            height: height,
+            data: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x < self.width && y < self.height {
+            let i = (y as usize * self.width as usize + x as usize) * 4;
+            self.data[i..i + 4].copy_from_slice(&color);
+        }
+    }
+
+    fn clear(&mut self, color: [u8; 4]) {
+        for pixel in self.data.chunks_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    // Keeps the pixels when the size is unchanged, otherwise clears them.
+    fn resize(&mut self, width: u32, height: u32) {
+        if width != self.width || height != self.height {
+            *self = Pixels::new(width, height);
+        }
+    }
+}
+
+/// Converts RGBA8 pixels to the native BGRX layout.
+fn rgba_to_bgrx(rgba: &[u8], bgrx: &mut [u8]) {
+    for (src, dst) in rgba.chunks(4).zip(bgrx.chunks_mut(4)) {
+        dst[0] = src[2];
+        dst[1] = src[1];
+        dst[2] = src[0];
+        dst[3] = 0xff;
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+enum Presenter {
+    X11(x11::X11Presenter),
+    Wayland(wayland::WaylandPresenter),
+}
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+impl Presenter {
+    fn new(window: &OriginalWinitWindow) -> Result<Self, FramebufferError> {
+        use winit::platform::unix::WindowExtUnix;
+
+        if let (Some(display), Some(surface)) = (window.wayland_display(), window.wayland_surface()) {
+            return Ok(Presenter::Wayland(unsafe { wayland::WaylandPresenter::new(display, surface)? }));
+        }
+        match (window.xlib_display(), window.xlib_window()) {
+            (Some(display), Some(xwin)) =>
+                Ok(Presenter::X11(unsafe { x11::X11Presenter::new(display, xwin)? })),
+            _ => Err(FramebufferError::Unsupported("unknown window system")),
+        }
+    }
+
+    fn present(&mut self, pixels: &[u8], width: u32, height: u32) -> Result<(), FramebufferError> {
+        match *self {
+            Presenter::X11(ref mut presenter) => presenter.present(pixels, width, height),
+            Presenter::Wayland(ref mut presenter) => presenter.present(pixels, width, height),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+mod x11 {
+    use std::os::raw::{c_char, c_int, c_uint, c_ulong, c_void};
+    use std::ptr;
+
+    use libc;
+    use x11_dl::xlib::{self, Xlib};
+    use x11_dl::xshm::{XShmSegmentInfo, Xext};
+
+    use super::{rgba_to_bgrx, FramebufferError};
+
+    struct Image {
+        image: *mut xlib::XImage,
+        // Boxed since Xlib keeps a pointer to the segment info.
+        shm: Option<Box<XShmSegmentInfo>>,
+        // Pixel storage when shared memory is not available.
+        data: Vec<u8>,
+        width: u32,
+        height: u32,
+    }
+
+    pub struct X11Presenter {
+        xlib: Xlib,
+        xext: Option<Xext>,
+        display: *mut xlib::Display,
+        window: c_ulong,
+        gc: xlib::GC,
+        visual: *mut xlib::Visual,
+        depth: c_int,
+        image: Option<Image>,
+    }
+
+    impl X11Presenter {
+        /// Creates a presenter on the display connection of the window.
+        pub unsafe fn new(display: *mut c_void, window: c_ulong) -> Result<Self, FramebufferError> {
+            let xlib = Xlib::open().map_err(|err| FramebufferError::Os(format!("{}", err)))?;
+            let display = display as *mut xlib::Display;
+
+            let mut attributes: xlib::XWindowAttributes = ::std::mem::zeroed();
+            (xlib.XGetWindowAttributes)(display, window, &mut attributes);
+            if attributes.depth != 24 && attributes.depth != 32 {
+                return Err(FramebufferError::Unsupported("X11 visuals other than true color"));
+            }
+
+            // Shared memory is optional, e.g. it is not available on remote displays.
+            let xext = Xext::open().ok()
+                .and_then(|xext| if (xext.XShmQueryExtension)(display) != 0 { Some(xext) } else { None });
+            let gc = (xlib.XCreateGC)(display, window, 0, ptr::null_mut());
+
+            Ok(X11Presenter {
+                xlib,
+                xext,
+                display,
+                window,
+                gc,
+                visual: attributes.visual,
+                depth: attributes.depth,
+                image: None,
+            })
+        }
+
+        pub fn present(&mut self, pixels: &[u8], width: u32, height: u32) -> Result<(), FramebufferError> {
+            let resized = match self.image {
+                Some(ref image) => image.width != width || image.height != height,
+                None => true,
+            };
+            if resized {
+                unsafe {
+                    if let Some(image) = self.image.take() {
+                        self.destroy_image(image);
+                    }
+                    let image = match self.create_shm_image(width, height) {
+                        Some(image) => image,
+                        None => self.create_image(width, height)?,
+                    };
+                    self.image = Some(image);
+                }
+            }
+
+            let image = self.image.as_mut().unwrap();
+            unsafe {
+                let stride = (*image.image).bytes_per_line as usize;
+                let data = (*image.image).data as *mut u8;
+                let row_len = width as usize * 4;
+                for (y, row) in pixels.chunks(row_len).enumerate() {
+                    let dst = ::std::slice::from_raw_parts_mut(data.offset((y * stride) as isize), row_len);
+                    rgba_to_bgrx(row, dst);
+                }
+
+                match (self.xext.as_ref(), image.shm.is_some()) {
+                    (Some(xext), true) => {
+                        (xext.XShmPutImage)(self.display, self.window, self.gc, image.image,
+                                            0, 0, 0, 0, width, height, xlib::False);
+                    }
+                    _ => {
+                        (self.xlib.XPutImage)(self.display, self.window, self.gc, image.image,
+                                              0, 0, 0, 0, width, height);
+                    }
+                }
+                (self.xlib.XFlush)(self.display);
+            }
+            Ok(())
+        }
+
+        unsafe fn create_shm_image(&self, width: u32, height: u32) -> Option<Image> {
+            let xext = self.xext.as_ref()?;
+            let mut shm = Box::new(XShmSegmentInfo {
+                shmseg: 0,
+                shmid: -1,
+                shmaddr: ptr::null_mut(),
+                readOnly: xlib::False,
+            });
+            let image = (xext.XShmCreateImage)(self.display, self.visual, self.depth as c_uint,
+                                               xlib::ZPixmap, ptr::null_mut(), &mut *shm, width, height);
+            if image.is_null() {
+                return None;
+            }
+
+            let size = (*image).bytes_per_line as usize * height as usize;
+            shm.shmid = libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600);
+            if shm.shmid < 0 {
+                (self.xlib.XDestroyImage)(image);
+                return None;
+            }
+            let addr = libc::shmat(shm.shmid, ptr::null(), 0);
+            // The segment is removed once it is detached by both processes.
+            libc::shmctl(shm.shmid, libc::IPC_RMID, ptr::null_mut());
+            if addr as isize == -1 {
+                (self.xlib.XDestroyImage)(image);
+                return None;
+            }
+            shm.shmaddr = addr as *mut c_char;
+            (*image).data = shm.shmaddr;
+
+            if (xext.XShmAttach)(self.display, &mut *shm) == 0 {
+                (*image).data = ptr::null_mut();
+                (self.xlib.XDestroyImage)(image);
+                libc::shmdt(addr);
+                return None;
+            }
+            (self.xlib.XSync)(self.display, xlib::False);
+
+            Some(Image { image, shm: Some(shm), data: vec![], width, height })
+        }
+
+        unsafe fn create_image(&self, width: u32, height: u32) -> Result<Image, FramebufferError> {
+            let mut data = vec![0u8; width as usize * height as usize * 4];
+            let image = (self.xlib.XCreateImage)(self.display, self.visual, self.depth as c_uint,
+                                                 xlib::ZPixmap, 0, data.as_mut_ptr() as *mut c_char,
+                                                 width, height, 32, 0);
+            if image.is_null() {
+                return Err(FramebufferError::Os("XCreateImage failed".into()));
+            }
+            Ok(Image { image, shm: None, data, width, height })
+        }
+
+        unsafe fn destroy_image(&self, mut image: Image) {
+            if let (Some(xext), Some(shm)) = (self.xext.as_ref(), image.shm.as_mut()) {
+                (xext.XShmDetach)(self.display, &mut **shm);
+                (self.xlib.XSync)(self.display, xlib::False);
+                libc::shmdt(shm.shmaddr as *const c_void);
+            }
+            // The pixel storage is not owned by Xlib.
+            (*image.image).data = ptr::null_mut();
+            (self.xlib.XDestroyImage)(image.image);
+            image.data.clear();
+        }
+    }
+
+    impl Drop for X11Presenter {
+        fn drop(&mut self) {
+            unsafe {
+                if let Some(image) = self.image.take() {
+                    self.destroy_image(image);
+                }
+                (self.xlib.XFreeGC)(self.display, self.gc);
+            }
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+mod wayland {
+    use std::fs::File;
+    use std::os::raw::c_void;
+    use std::os::unix::fs::FileExt;
+    use std::os::unix::io::AsRawFd;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use tempfile;
+    use wayland_client::{Display, EventQueue, GlobalManager, Proxy};
+    use wayland_client::protocol::wl_buffer::{self, WlBuffer};
+    use wayland_client::protocol::wl_shm::{Format, WlShm};
+    use wayland_client::protocol::wl_shm_pool::WlShmPool;
+    use wayland_client::protocol::wl_surface::WlSurface;
+
+    use super::{rgba_to_bgrx, FramebufferError};
+
+    struct Buffer {
+        // The shared memory of the pool.
+        file: File,
+        pool: WlShmPool,
+        buffer: WlBuffer,
+        width: u32,
+        height: u32,
+        // Set while the compositor may read the buffer,
+        // from attaching it until it is released.
+        busy: Arc<AtomicBool>,
+    }
+
+    impl Drop for Buffer {
+        fn drop(&mut self) {
+            self.buffer.destroy();
+            self.pool.destroy();
+        }
+    }
+
+    // The number of buffers, such that one can be written while the other is read.
+    const BUFFER_COUNT: usize = 2;
+
+    pub struct WaylandPresenter {
+        // Dropped in declaration order, the buffers before the connection.
+        buffers: Vec<Buffer>,
+        surface: WlSurface,
+        shm: WlShm,
+        event_queue: EventQueue,
+        display: Display,
+        // Converted pixels, reused between frames.
+        data: Vec<u8>,
+    }
+
+    impl WaylandPresenter {
+        /// Creates a presenter on the display connection of the window.
+        pub unsafe fn new(display: *mut c_void, surface: *mut c_void) -> Result<Self, FramebufferError> {
+            let (display, mut event_queue) = Display::from_external_display(display as *mut _);
+            let globals = GlobalManager::new(&display);
+            event_queue.sync_roundtrip()?;
+            let shm = globals.instantiate_exact::<WlShm, _>(1, |shm| shm.implement_dummy())
+                .map_err(|err| FramebufferError::Os(format!("{:?}", err)))?;
+            let surface: WlSurface = Proxy::<WlSurface>::from_c_ptr(surface as *mut _).into();
+
+            Ok(WaylandPresenter {
+                buffers: vec![],
+                surface,
+                shm,
+                event_queue,
+                display,
+                data: vec![],
+            })
+        }
+
+        /// Presents the pixels in a buffer that is not read by the compositor.
+        ///
+        /// The frame is skipped when all buffers are still read.
+        pub fn present(&mut self, pixels: &[u8], width: u32, height: u32) -> Result<(), FramebufferError> {
+            // Receive the release events of the buffers.
+            self.event_queue.dispatch_pending()?;
+            self.buffers.retain(|buffer| buffer.width == width && buffer.height == height);
+
+            let index = match self.buffers.iter().position(|buffer| !buffer.busy.load(Ordering::SeqCst)) {
+                Some(index) => index,
+                None if self.buffers.len() < BUFFER_COUNT => {
+                    let buffer = self.create_buffer(width, height)?;
+                    self.buffers.push(buffer);
+                    self.buffers.len() - 1
+                }
+                None => {
+                    trace!("skipping frame, all buffers are read by the compositor");
+                    return Ok(());
+                }
+            };
+            let buffer = &self.buffers[index];
+
+            self.data.resize(pixels.len(), 0);
+            rgba_to_bgrx(pixels, &mut self.data);
+            buffer.file.write_all_at(&self.data, 0)?;
+
+            buffer.busy.store(true, Ordering::SeqCst);
+            self.surface.attach(Some(&buffer.buffer), 0, 0);
+            self.surface.damage(0, 0, width as i32, height as i32);
+            self.surface.commit();
+            self.display.flush()?;
+            Ok(())
+        }
+
+        fn create_buffer(&self, width: u32, height: u32) -> Result<Buffer, FramebufferError> {
+            let stride = width * 4;
+            let size = stride * height;
+            let file = tempfile::tempfile()?;
+            file.set_len(size as u64)?;
+
+            let pool = self.shm.create_pool(file.as_raw_fd(), size as i32, |pool| pool.implement_dummy())
+                .map_err(|_| FramebufferError::Os("Failed to create shm pool".into()))?;
+            let busy = Arc::new(AtomicBool::new(false));
+            let released = busy.clone();
+            let buffer = pool.create_buffer(0, width as i32, height as i32, stride as i32,
+                                            Format::Xrgb8888, |buffer| buffer.implement_closure(
+                                                move |event, _| match event {
+                                                    wl_buffer::Event::Release =>
+                                                        released.store(false, Ordering::SeqCst),
+                                                    _ => {}
+                                                }, ()))
+                .map_err(|_| FramebufferError::Os("Failed to create shm buffer".into()))?;
+            Ok(Buffer { file, pool, buffer, width, height, busy })
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+struct Presenter {
+    hwnd: ::winapi::shared::windef::HWND,
+    // Converted pixels, reused between frames.
+    data: Vec<u8>,
+}
+
+#[cfg(target_os = "windows")]
+impl Presenter {
+    fn new(window: &OriginalWinitWindow) -> Result<Self, FramebufferError> {
+        use winit::platform::windows::WindowExtWindows;
+
+        Ok(Presenter { hwnd: window.hwnd() as _, data: vec![] })
+    }
+
+    fn present(&mut self, pixels: &[u8], width: u32, height: u32) -> Result<(), FramebufferError> {
+        use std::mem;
+        use winapi::um::wingdi::{
+            StretchDIBits, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, SRCCOPY,
+        };
+        use winapi::um::winuser::{GetDC, ReleaseDC};
+
+        self.data.resize(pixels.len(), 0);
+        rgba_to_bgrx(pixels, &mut self.data);
+        unsafe {
+            let mut info: BITMAPINFO = mem::zeroed();
+            info.bmiHeader.biSize = mem::size_of::<BITMAPINFOHEADER>() as u32;
+            info.bmiHeader.biWidth = width as i32;
+            // Negative height for rows from the top.
+            info.bmiHeader.biHeight = -(height as i32);
+            info.bmiHeader.biPlanes = 1;
+            info.bmiHeader.biBitCount = 32;
+            info.bmiHeader.biCompression = BI_RGB;
+
+            let hdc = GetDC(self.hwnd);
+            if hdc.is_null() {
+                return Err(FramebufferError::Os("GetDC failed".into()));
+            }
+            StretchDIBits(hdc, 0, 0, width as i32, height as i32, 0, 0, width as i32, height as i32,
+                          self.data.as_ptr() as *const _, &info, DIB_RGB_COLORS, SRCCOPY);
+            ReleaseDC(self.hwnd, hdc);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+              target_os = "netbsd", target_os = "openbsd", target_os = "windows")))]
+struct Presenter;
+
+#[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+              target_os = "netbsd", target_os = "openbsd", target_os = "windows")))]
+impl Presenter {
+    fn new(_window: &OriginalWinitWindow) -> Result<Self, FramebufferError> {
+        Err(FramebufferError::Unsupported("this platform"))
+    }
+
+    fn present(&mut self, _pixels: &[u8], _width: u32, _height: u32) -> Result<(), FramebufferError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgba_to_bgrx_swaps_red_and_blue() {
+        let rgba = [1, 2, 3, 4, 5, 6, 7, 8];
+        let mut bgrx = [0; 8];
+        rgba_to_bgrx(&rgba, &mut bgrx);
+        assert_eq!(bgrx, [3, 2, 1, 0xff, 7, 6, 5, 0xff]);
+    }
+
+    #[test]
+    fn set_pixel() {
+        let mut pixels = Pixels::new(3, 2);
+        pixels.set_pixel(2, 1, [1, 2, 3, 4]);
+        assert_eq!(&pixels.data[20..24], &[1, 2, 3, 4]);
+        assert_eq!(pixels.data.iter().filter(|&&x| x != 0).count(), 4);
+        // Outside the framebuffer.
+        pixels.set_pixel(3, 0, [5, 5, 5, 5]);
+        pixels.set_pixel(0, 2, [5, 5, 5, 5]);
+        assert!(!pixels.data.contains(&5));
+    }
+
+    #[test]
+    fn clear() {
+        let mut pixels = Pixels::new(2, 2);
+        pixels.clear([1, 2, 3, 4]);
+        assert_eq!(pixels.data, [1, 2, 3, 4].repeat(4));
+    }
+
+    #[test]
+    fn resize() {
+        let mut pixels = Pixels::new(2, 2);
+        pixels.clear([1, 2, 3, 4]);
+        pixels.resize(2, 2);
+        assert_eq!(pixels.data, [1, 2, 3, 4].repeat(4));
+        pixels.resize(3, 1);
+        assert_eq!((pixels.width, pixels.height), (3, 1));
+        assert_eq!(pixels.data, vec![0; 12]);
+        pixels.resize(0, 1);
+        assert!(pixels.data.is_empty());
+    }
+}
@@ -0,0 +1,789 @@
+//! Translates winit events to Piston input.
+//!
+//! The input state of a window is kept apart from the winit window,
+//! such that events can be translated without a display, e.g. in tests.
+//! The window passes its scale factor and applies the effects of the emitted input
+//! on itself, e.g. resizing the OpenGL context.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Instant;
+
+use input::{
+    keyboard,
+    ButtonArgs,
+    ButtonState,
+    CloseArgs,
+    Event,
+    MouseButton,
+    Button,
+    Input,
+    FileDrag,
+    ResizeArgs,
+    Key,
+    Motion,
+    Touch,
+    TouchArgs,
+};
+use winit::event::{
+    DeviceId,
+    ElementState,
+    Event as WinitEvent,
+    KeyboardInput,
+    MouseButton as WinitMouseButton,
+    Touch as WinitTouch,
+};
+
+use gesture::{GestureRecognizer, Gesture, GESTURE_EVENT_ID};
+use {
+    cursor_position,
+    map_key,
+    map_modifiers,
+    map_mouse,
+    motion_delta,
+    unmap_mouse,
+    window_size,
+    DebugEvent,
+    ScrollDelta,
+    ScrollPolicy,
+    TextFilter,
+    USER_EVENT_ID,
+};
+
+/// The input state of a window.
+pub struct InputState {
+    // Inputs to emit before popping the next winit event.
+    pub queued_events: VecDeque<Input>,
+    // Keys currently held down, by scancode.
+    pub held_keys: HashMap<u32, Key>,
+    // The modifier keys currently held down.
+    pub modifiers: keyboard::ModifierKey,
+    // Whether key presses repeated by the OS are emitted.
+    pub key_repeat: bool,
+    // Whether the last key press was repeated by the OS.
+    pub is_repeat: bool,
+    // Selects which received characters are emitted as text.
+    pub text_filter: TextFilter,
+    // Converts scroll deltas to scroll amounts.
+    pub scroll_policy: ScrollPolicy,
+    // Overrides the mapping of `MouseButton::Other` codes.
+    pub mouse_overrides: HashMap<u8, MouseButton>,
+    // The delta of the last scroll, in the unit of the device.
+    pub last_scroll: Option<ScrollDelta>,
+
+    // Set in locked cursor mode,
+    // to get relative mouse events.
+    pub is_capturing_cursor: bool,
+    // Whether the cursor is grabbed, in confined or locked mode.
+    // When locked, relative motion then comes from raw device events,
+    // otherwise capturing is faked by warping the cursor.
+    pub cursor_grabbed: bool,
+    // Set when the cursor moved while capturing is faked,
+    // such that the window warps it back to the center.
+    pub recenter_cursor: bool,
+    // Stores the last known cursor position.
+    pub last_cursor_pos: Option<[f64; 2]>,
+    // Stores relative coordinates to emit on next poll.
+    pub mouse_relative: Option<(f64, f64)>,
+    // Used to emit cursor event after enter/leave.
+    pub cursor_pos: Option<[f64; 2]>,
+    // Logs untranslated winit events when enabled.
+    pub trace_events: bool,
+    // Touch device ids, numbered in the order the devices are first touched.
+    pub touch_devices: HashMap<DeviceId, i64>,
+    // Whether the primary touch emulates the mouse.
+    pub touch_mouse: bool,
+    // The device and id of the touch emulating the mouse.
+    pub primary_touch: Option<(i64, u64)>,
+    // Recognizes gestures from touch input, when enabled.
+    pub gestures: Option<GestureRecognizer>,
+}
+
+impl InputState {
+    pub fn new() -> InputState {
+        InputState {
+            queued_events: VecDeque::new(),
+            held_keys: HashMap::new(),
+            modifiers: keyboard::ModifierKey::NO_MODIFIER,
+            key_repeat: true,
+            is_repeat: false,
+            text_filter: TextFilter::Sdl,
+            scroll_policy: ScrollPolicy::default(),
+            mouse_overrides: HashMap::new(),
+            last_scroll: None,
+
+            is_capturing_cursor: false,
+            cursor_grabbed: false,
+            recenter_cursor: false,
+            last_cursor_pos: None,
+            mouse_relative: None,
+            cursor_pos: None,
+            trace_events: false,
+            touch_devices: HashMap::new(),
+            touch_mouse: false,
+            primary_touch: None,
+            gestures: None,
+        }
+    }
+
+    /// Returns the next event, translating the winit events at the front of `events`.
+    ///
+    /// Returns `None` when all events are handled, such that the event loop must be pumped.
+    pub fn poll<T: 'static + Send + Sync>(
+        &mut self,
+        events: &mut VecDeque<WinitEvent<'static, T>>,
+        scale_factor: f64
+    ) -> Option<Event> {
+        use winit::event::WindowEvent as WE;
+
+        // Loop to skip unknown events.
+        loop {
+            if let Some(input) = self.pre_pop_front_event() {
+                return Some(Event::Input(input, None));
+            }
+
+            if let Some(gesture) = self.pop_gesture() {
+                return Some(Event::Custom(GESTURE_EVENT_ID, Arc::new(gesture), None));
+            }
+
+            let ev = events.pop_front()?;
+
+            if self.is_capturing_cursor &&
+               self.last_cursor_pos.is_none() {
+                if let WinitEvent::WindowEvent {
+                    event: WE::CursorMoved{ position, ..}, ..
+                } = ev {
+                    // Ignore this event since mouse positions
+                    // should not be emitted when capturing cursor.
+                    self.last_cursor_pos = Some(cursor_position(position, scale_factor));
+                    continue;
+                }
+            }
+
+            if let WinitEvent::UserEvent(user_event) = ev {
+                return Some(Event::Custom(USER_EVENT_ID, Arc::new(user_event), None));
+            }
+
+            let mut unknown = false;
+            let input = self.handle_event(&ev, scale_factor, &mut unknown);
+            if unknown {continue};
+            // Consecutive characters are coalesced into one text event.
+            let input = match input {
+                Some(Input::Text(text)) => Some(Input::Text(self.coalesce_text(events, text))),
+                input => input,
+            };
+            return input.map(|x| Event::Input(x, None));
+        }
+    }
+
+    /// Translates a winit event to Piston input, see `WinitWindow::handle_event`.
+    ///
+    /// Positions are converted to logical pixels with the scale factor of the window.
+    pub fn handle_event<T>(
+        &mut self,
+        ev: &WinitEvent<T>,
+        scale_factor: f64,
+        unknown: &mut bool
+    ) -> Option<Input> {
+        let mut consumed = false;
+        let input = self.translate_event(ev, scale_factor, unknown, &mut consumed);
+        if consumed {
+            *unknown = true;
+            trace!("consumed event: winit_event={:?}", DebugEvent(ev));
+        } else if *unknown {
+            if self.trace_events {
+                debug!("untranslated event: winit_event={:?}", DebugEvent(ev));
+            }
+        } else {
+            trace!("translated event: winit_event={:?} piston_input={:?}", DebugEvent(ev), input);
+        }
+        if let (Some(gestures), Some(&Input::Move(Motion::Touch(ref args)))) =
+            (self.gestures.as_mut(), input.as_ref()) {
+            gestures.touch(args, Instant::now());
+        }
+        input
+    }
+
+    // Sets `consumed` for recognized events that are not emitted as input.
+    fn translate_event<T>(
+        &mut self,
+        ev: &WinitEvent<T>,
+        scale_factor: f64,
+        unknown: &mut bool,
+        consumed: &mut bool
+    ) -> Option<Input> {
+        use winit::event::Event as E;
+        use winit::event::WindowEvent as WE;
+        use winit::event::{DeviceEvent, MouseScrollDelta, TouchPhase};
+
+        match ev {
+            E::WindowEvent {
+                event: WE::Resized(ref size), ..
+            } => Some(Input::Resize(ResizeArgs {
+                window_size: window_size(*size, scale_factor),
+                draw_size: [size.width, size.height],
+            })),
+            E::WindowEvent {
+                event: WE::ReceivedCharacter(ref ch), ..
+            } => {
+                if !self.text_filter.accepts(*ch) {
+                    *consumed = true;
+                    return None;
+                }
+                Some(Input::Text(ch.to_string()))
+            },
+            E::WindowEvent {
+                event: WE::Focused(ref focused), ..
+            } => {
+                if !*focused {
+                    // Release events of held keys go to the newly focused window,
+                    // so they are synthesized here.
+                    for (scancode, key) in self.held_keys.drain() {
+                        self.queued_events.push_back(Input::Button(ButtonArgs {
+                            state: ButtonState::Release,
+                            button: Button::Keyboard(key),
+                            scancode: Some(scancode as i32),
+                        }));
+                    }
+                    self.modifiers = keyboard::ModifierKey::NO_MODIFIER;
+                }
+                Some(Input::Focus(*focused))
+            }
+            E::WindowEvent {
+                event: WE::ModifiersChanged(ref modifiers), ..
+            } => {
+                // Modifier state is read with `get_modifiers` instead of emitted.
+                self.modifiers = map_modifiers(*modifiers);
+                *consumed = true;
+                None
+            }
+            E::WindowEvent {
+                event: WE::KeyboardInput{
+                    input: KeyboardInput{
+                        state: ElementState::Pressed,
+                        ref virtual_keycode, ref scancode, ..
+                    }, ..
+                }, ..
+            } => {
+                // Keys without a key code are identified by their scancode.
+                let piston_key = virtual_keycode.map(map_key).unwrap_or(Key::Unknown);
+                // A press of a key that is already held is repeated by the OS.
+                self.is_repeat = self.held_keys.insert(*scancode, piston_key).is_some();
+                if self.is_repeat && !self.key_repeat {
+                    *consumed = true;
+                    return None;
+                }
+                Some(Input::Button(ButtonArgs {
+                    state: ButtonState::Press,
+                    button: Button::Keyboard(piston_key),
+                    scancode: Some(*scancode as i32),
+                }))
+            },
+            E::WindowEvent {
+                 event: WE::KeyboardInput{
+                     input: KeyboardInput{
+                         state: ElementState::Released,
+                         ref virtual_keycode, ref scancode, ..
+                     }, ref is_synthetic, ..
+                 }, ..
+             } => {
+                let held = self.held_keys.remove(scancode).is_some();
+                if *is_synthetic && !held {
+                    // Already released when focus was lost.
+                    *consumed = true;
+                    return None;
+                }
+                Some(Input::Button(ButtonArgs {
+                    state: ButtonState::Release,
+                    button: Button::Keyboard(virtual_keycode.map(map_key).unwrap_or(Key::Unknown)),
+                    scancode: Some(*scancode as i32),
+                }))},
+            E::WindowEvent {
+                event: WE::Touch(WinitTouch {
+                    ref device_id, ref phase, ref location, ref force, ref id
+                }), ..
+            } => {
+                let device = device_number(&mut self.touch_devices, *device_id);
+                let pos = cursor_position(*location, scale_factor);
+                // Devices that do not report force are touched at full pressure.
+                let pressure = force.map(|force| force.normalized()).unwrap_or(1.0);
+                let touch = match phase {
+                    TouchPhase::Started => Touch::Start,
+                    TouchPhase::Moved => Touch::Move,
+                    TouchPhase::Ended => Touch::End,
+                    TouchPhase::Cancelled => Touch::Cancel
+                };
+
+                if self.touch_mouse {
+                    self.emulate_mouse(device, *id, pos, touch);
+                }
+                Some(Input::Move(Motion::Touch(TouchArgs::new(device, *id as i64, pos, pressure, touch))))
+            },
+            E::WindowEvent {
+                event: WE::CursorMoved{ref position, ..}, ..
+            } => {
+                let [x, y] = cursor_position(*position, scale_factor);
+
+                if self.is_capturing_cursor && self.cursor_grabbed {
+                    // Relative motion is emitted from raw device events.
+                    self.last_cursor_pos = Some([x, y]);
+                    *consumed = true;
+                    return None;
+                }
+
+                if let Some(pos) = self.last_cursor_pos {
+                    let dx = x - pos[0];
+                    let dy = y - pos[1];
+                    if self.is_capturing_cursor {
+                        self.last_cursor_pos = Some([x as f64, y as f64]);
+                        self.recenter_cursor = true;
+                        // Skip normal mouse movement and emit relative motion only.
+                        return Some(Input::Move(Motion::MouseRelative([dx as f64, dy as f64])));
+                    }
+                    // Send relative mouse movement next time.
+                    self.mouse_relative = Some((dx as f64, dy as f64));
+                }
+
+                self.last_cursor_pos = Some([x as f64, y as f64]);
+                Some(Input::Move(Motion::MouseCursor([x as f64, y as f64])))
+            }
+            E::DeviceEvent {
+                event: DeviceEvent::MouseMotion{delta: (dx, dy)}, ..
+            } if self.is_capturing_cursor && self.cursor_grabbed => {
+                // Scaled like the warped cursor positions.
+                let delta = motion_delta((*dx, *dy), scale_factor);
+                Some(Input::Move(Motion::MouseRelative(delta)))
+            }
+            E::WindowEvent {
+                event: WE::CursorEntered{..}, ..
+            } => Some(Input::Cursor(true)),
+            E::WindowEvent {
+                event: WE::CursorLeft{..}, ..
+            } => Some(Input::Cursor(false)),
+            E::WindowEvent {
+                event: WE::MouseWheel{ref delta, ..}, ..
+            } => {
+                let delta = match *delta {
+                    MouseScrollDelta::PixelDelta(pos) => ScrollDelta::Pixels([pos.x, pos.y]),
+                    MouseScrollDelta::LineDelta(x, y) => ScrollDelta::Lines([x as f64, y as f64]),
+                };
+                self.last_scroll = Some(delta);
+                Some(Input::Move(Motion::MouseScroll(self.scroll_policy.apply(delta))))
+            }
+            E::WindowEvent {
+                event: WE::MouseInput{state: ElementState::Pressed, ref button, ..}, ..
+            } => Some(Input::Button(ButtonArgs {
+                state: ButtonState::Press,
+                button: Button::Mouse(self.map_mouse(*button)),
+                scancode: None,
+            })),
+            E::WindowEvent {
+                event: WE::MouseInput{state: ElementState::Released, ref button, ..}, ..
+            } => Some(Input::Button(ButtonArgs {
+                state: ButtonState::Release,
+                button: Button::Mouse(self.map_mouse(*button)),
+                scancode: None,
+            })),
+            E::WindowEvent {
+                event: WE::HoveredFile(ref path), ..
+            } => Some(Input::FileDrag(FileDrag::Hover(path.clone()))),
+            E::WindowEvent {
+                event: WE::DroppedFile(ref path), ..
+            } => Some(Input::FileDrag(FileDrag::Drop(path.clone()))),
+            E::WindowEvent {
+                event: WE::HoveredFileCancelled, ..
+            } => Some(Input::FileDrag(FileDrag::Cancel)),
+            E::WindowEvent { event: WE::CloseRequested, .. } => Some(Input::Close(CloseArgs)),
+            _ => {
+                *unknown = true;
+                None
+            }
+        }
+    }
+
+    /// Pops the next recognized gesture.
+    ///
+    /// Returns `None` when gestures are disabled.
+    pub fn pop_gesture(&mut self) -> Option<Gesture> {
+        self.gestures.as_mut().and_then(|gestures| gestures.pop(Instant::now()))
+    }
+
+    /// Maps winit's mouse button to Piston's mouse button, following the overrides.
+    pub fn map_mouse(&self, mouse_button: WinitMouseButton) -> MouseButton {
+        if let WinitMouseButton::Other(code) = mouse_button {
+            if let Some(&button) = self.mouse_overrides.get(&code) {
+                return button;
+            }
+        }
+        map_mouse(mouse_button)
+    }
+
+    /// Maps Piston's mouse button to winit's mouse button, following the overrides.
+    pub fn unmap_mouse(&self, mouse_button: MouseButton) -> Option<WinitMouseButton> {
+        let overridden = self.mouse_overrides.iter()
+            .filter(|&(_, &button)| button == mouse_button)
+            .map(|(&code, _)| code)
+            .min();
+        if let Some(code) = overridden {
+            return Some(WinitMouseButton::Other(code));
+        }
+        match unmap_mouse(mouse_button) {
+            // The code is overridden to another button.
+            Some(WinitMouseButton::Other(code)) if self.mouse_overrides.contains_key(&code) => None,
+            button => button,
+        }
+    }
+
+    // Appends the received characters at the front of `events` to the text.
+    fn coalesce_text<T>(&self, events: &mut VecDeque<WinitEvent<'static, T>>, mut text: String) -> String {
+        use winit::event::WindowEvent as WE;
+
+        loop {
+            let ch = match events.front() {
+                Some(&WinitEvent::WindowEvent {
+                    event: WE::ReceivedCharacter(ch), ..
+                }) => ch,
+                _ => return text,
+            };
+            events.pop_front();
+            if self.text_filter.accepts(ch) {
+                text.push(ch);
+            }
+        }
+    }
+
+    // Queues mouse inputs following the primary touch.
+    fn emulate_mouse(&mut self, device: i64, id: u64, pos: [f64; 2], touch: Touch) {
+        match (touch, self.primary_touch) {
+            (Touch::Start, None) => self.primary_touch = Some((device, id)),
+            (_, Some(primary)) if primary == (device, id) => {}
+            _ => return,
+        }
+        // Relative motion of the next cursor move starts at the emulated position.
+        self.last_cursor_pos = Some(pos);
+        self.queued_events.push_back(Input::Move(Motion::MouseCursor(pos)));
+        let state = match touch {
+            Touch::Start => ButtonState::Press,
+            Touch::Move => return,
+            Touch::End | Touch::Cancel => {
+                self.primary_touch = None;
+                ButtonState::Release
+            }
+        };
+        self.queued_events.push_back(Input::Button(ButtonArgs {
+            state: state,
+            button: Button::Mouse(MouseButton::Left),
+            scancode: None,
+        }));
+    }
+
+    // These events are emitted before popping a new event from the queue.
+    // This is because Piston handles some events separately.
+    fn pre_pop_front_event(&mut self) -> Option<Input> {
+        // Check for a pending mouse cursor move event.
+        if let Some(pos) = self.cursor_pos {
+            self.cursor_pos = None;
+            return Some(Input::Move(Motion::MouseCursor(pos)));
+        }
+
+        // Check for a pending relative mouse move event.
+        if let Some((x, y)) = self.mouse_relative {
+            self.mouse_relative = None;
+            return Some(Input::Move(Motion::MouseRelative([x, y])));
+        }
+
+        // Check for synthesized inputs.
+        if let Some(input) = self.queued_events.pop_front() {
+            return Some(input);
+        }
+
+        None
+    }
+}
+
+// Numbers devices in the order they are first seen.
+fn device_number<K: Eq + Hash>(devices: &mut HashMap<K, i64>, device: K) -> i64 {
+    let next = devices.len() as i64;
+    *devices.entry(device).or_insert(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::{LogicalPosition, PhysicalPosition, PhysicalSize};
+    use winit::event::{Force, ModifiersState, MouseScrollDelta, TouchPhase, VirtualKeyCode, WindowEvent};
+    use winit::window::WindowId;
+
+    #[allow(deprecated)]
+    fn key_event(
+        state: ElementState,
+        scancode: u32,
+        key: Option<VirtualKeyCode>
+    ) -> WinitEvent<'static, ()> {
+        WinitEvent::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::KeyboardInput {
+                device_id: unsafe { DeviceId::dummy() },
+                input: KeyboardInput {
+                    scancode: scancode,
+                    state: state,
+                    virtual_keycode: key,
+                    modifiers: ModifiersState::empty(),
+                },
+                is_synthetic: false,
+            },
+        }
+    }
+
+    #[allow(deprecated)]
+    fn scroll_event(delta: MouseScrollDelta) -> WinitEvent<'static, ()> {
+        WinitEvent::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::MouseWheel {
+                device_id: unsafe { DeviceId::dummy() },
+                delta: delta,
+                phase: TouchPhase::Moved,
+                modifiers: ModifiersState::empty(),
+            },
+        }
+    }
+
+    fn char_event(ch: char) -> WinitEvent<'static, ()> {
+        WinitEvent::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::ReceivedCharacter(ch),
+        }
+    }
+
+    #[test]
+    fn coalesce_text() {
+        let mut state = InputState::new();
+        let mut events: VecDeque<_> = "\u{8}a\rbc".chars().map(char_event).collect();
+        events.push_back(key_event(ElementState::Pressed, 30, Some(VirtualKeyCode::A)));
+        events.push_back(char_event('d'));
+        match state.poll(&mut events, 1.0) {
+            Some(Event::Input(Input::Text(text), None)) => assert_eq!(text, "abc"),
+            event => panic!("unexpected event {:?}", event),
+        }
+        match state.poll(&mut events, 1.0) {
+            Some(Event::Input(Input::Button(_), None)) => {}
+            event => panic!("unexpected event {:?}", event),
+        }
+        match state.poll(&mut events, 1.0) {
+            Some(Event::Input(Input::Text(text), None)) => assert_eq!(text, "d"),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(state.poll(&mut events, 1.0).is_none());
+
+        // Filtered characters are not emitted as empty text.
+        let mut events: VecDeque<_> = "\r\n\t".chars().map(char_event).collect();
+        assert!(state.poll(&mut events, 1.0).is_none());
+        assert!(events.is_empty());
+    }
+
+    fn touch_event(id: u64, phase: TouchPhase, pos: [f64; 2], force: Option<Force>) -> WinitEvent<'static, ()> {
+        WinitEvent::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::Touch(WinitTouch {
+                device_id: unsafe { DeviceId::dummy() },
+                phase: phase,
+                location: PhysicalPosition::new(pos[0], pos[1]),
+                force: force,
+                id: id,
+            }),
+        }
+    }
+
+    #[allow(deprecated)]
+    fn cursor_event(pos: [f64; 2]) -> WinitEvent<'static, ()> {
+        WinitEvent::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::CursorMoved {
+                device_id: unsafe { DeviceId::dummy() },
+                position: PhysicalPosition::new(pos[0], pos[1]),
+                modifiers: ModifiersState::empty(),
+            },
+        }
+    }
+
+    #[test]
+    fn touch() {
+        let forces = [
+            (None, 1.0),
+            (Some(Force::Normalized(0.25)), 0.25),
+            (Some(Force::Calibrated { force: 2.0, max_possible_force: 4.0, altitude_angle: None }), 0.5),
+        ];
+        for &scale_factor in &[1.0, 1.5, 2.0] {
+            for &(force, pressure) in &forces {
+                let mut state = InputState::new();
+                let mut unknown = false;
+                let ev = touch_event(3, TouchPhase::Started, [300.0, 150.0], force);
+                let args = match state.handle_event(&ev, scale_factor, &mut unknown) {
+                    Some(Input::Move(Motion::Touch(args))) => args,
+                    input => panic!("unexpected input {:?}", input),
+                };
+                assert_eq!(args.device, 0);
+                assert_eq!(args.id, 3);
+                assert_eq!(args.position(), [300.0 / scale_factor, 150.0 / scale_factor]);
+                assert_eq!(args.pressure(), pressure);
+                assert_eq!(args.touch, Touch::Start);
+            }
+        }
+    }
+
+    #[test]
+    fn device_numbers() {
+        let mut devices = HashMap::new();
+        assert_eq!(device_number(&mut devices, 7), 0);
+        assert_eq!(device_number(&mut devices, 3), 1);
+        assert_eq!(device_number(&mut devices, 7), 0);
+        assert_eq!(device_number(&mut devices, 5), 2);
+    }
+
+    #[test]
+    fn touch_mouse() {
+        let mut state = InputState::new();
+        state.touch_mouse = true;
+        let mut unknown = false;
+        let mut events = VecDeque::new();
+        events.push_back(touch_event(3, TouchPhase::Started, [10.0, 20.0], None));
+        events.push_back(cursor_event([15.0, 20.0]));
+        let inputs: Vec<_> = (0..5).filter_map(|_| match state.poll(&mut events, 1.0) {
+            Some(Event::Input(input, _)) => Some(input),
+            _ => None,
+        }).collect();
+        assert_eq!(inputs, vec![
+            Input::Move(Motion::Touch(TouchArgs::new(0, 3, [10.0, 20.0], 1.0, Touch::Start))),
+            Input::Move(Motion::MouseCursor([10.0, 20.0])),
+            Input::Button(ButtonArgs {
+                state: ButtonState::Press,
+                button: Button::Mouse(MouseButton::Left),
+                scancode: None,
+            }),
+            Input::Move(Motion::MouseCursor([15.0, 20.0])),
+            // Relative to the emulated cursor position.
+            Input::Move(Motion::MouseRelative([5.0, 0.0])),
+        ]);
+        assert!(state.handle_event(&touch_event(3, TouchPhase::Ended, [10.0, 20.0], None), 1.0, &mut unknown)
+            .is_some());
+        assert_eq!(state.queued_events.pop_back(), Some(Input::Button(ButtonArgs {
+            state: ButtonState::Release,
+            button: Button::Mouse(MouseButton::Left),
+            scancode: None,
+        })));
+        assert_eq!(state.primary_touch, None);
+    }
+
+    #[test]
+    fn gestures() {
+        let mut state = InputState::new();
+        state.gestures = Some(GestureRecognizer::new(Default::default()));
+        let mut events = VecDeque::new();
+        // A tap.
+        events.push_back(touch_event(0, TouchPhase::Started, [10.0, 10.0], None));
+        events.push_back(touch_event(0, TouchPhase::Ended, [12.0, 10.0], None));
+        // Lifted too far away to tap.
+        events.push_back(touch_event(0, TouchPhase::Started, [100.0, 0.0], None));
+        events.push_back(touch_event(0, TouchPhase::Ended, [150.0, 0.0], None));
+        // A pinch at scale 2.0, in physical pixels.
+        events.push_back(touch_event(0, TouchPhase::Started, [0.0, 0.0], None));
+        events.push_back(touch_event(1, TouchPhase::Started, [20.0, 0.0], None));
+        events.push_back(touch_event(1, TouchPhase::Moved, [40.0, 0.0], None));
+        let mut gestures = vec![];
+        while let Some(event) = state.poll(&mut events, 2.0) {
+            if let Event::Custom(id, gesture, _) = event {
+                assert_eq!(id, GESTURE_EVENT_ID);
+                gestures.push(*gesture.downcast_ref::<Gesture>().unwrap());
+            }
+        }
+        assert_eq!(gestures, vec![Gesture::Tap([6.0, 5.0]), Gesture::Pinch(2.0)]);
+    }
+
+    #[test]
+    fn resize_is_logical() {
+        for &scale_factor in &[1.0, 1.5, 2.0] {
+            let mut state = InputState::new();
+            let mut unknown = false;
+            let ev: WinitEvent<()> = WinitEvent::WindowEvent {
+                window_id: unsafe { WindowId::dummy() },
+                event: WindowEvent::Resized(PhysicalSize::new(1200, 900)),
+            };
+            assert_eq!(state.handle_event(&ev, scale_factor, &mut unknown), Some(Input::Resize(ResizeArgs {
+                window_size: [1200.0 / scale_factor, 900.0 / scale_factor],
+                draw_size: [1200, 900],
+            })));
+        }
+    }
+
+    #[test]
+    fn key_repeat() {
+        let mut state = InputState::new();
+        let press = key_event(ElementState::Pressed, 30, Some(VirtualKeyCode::A));
+        let release = key_event(ElementState::Released, 30, Some(VirtualKeyCode::A));
+        let mut unknown = false;
+        assert!(state.handle_event(&press, 1.0, &mut unknown).is_some());
+        assert!(!state.is_repeat);
+        assert!(state.handle_event(&press, 1.0, &mut unknown).is_some());
+        assert!(state.is_repeat);
+        assert!(state.handle_event(&release, 1.0, &mut unknown).is_some());
+        assert!(state.handle_event(&press, 1.0, &mut unknown).is_some());
+        assert!(!state.is_repeat);
+        assert!(!unknown);
+    }
+
+    #[test]
+    fn key_repeat_suppressed() {
+        let mut state = InputState::new();
+        state.key_repeat = false;
+        let press = key_event(ElementState::Pressed, 30, Some(VirtualKeyCode::A));
+        let release = key_event(ElementState::Released, 30, Some(VirtualKeyCode::A));
+        let mut unknown = false;
+        assert!(state.handle_event(&press, 1.0, &mut unknown).is_some());
+        assert!(!unknown);
+        assert!(state.handle_event(&press, 1.0, &mut unknown).is_none());
+        assert!(unknown);
+        // Keys are identified by scancode, also without a key code.
+        let other = key_event(ElementState::Pressed, 200, None);
+        let mut unknown = false;
+        assert!(state.handle_event(&other, 1.0, &mut unknown).is_some());
+        assert!(state.handle_event(&other, 1.0, &mut unknown).is_none());
+        let mut unknown = false;
+        assert!(state.handle_event(&release, 1.0, &mut unknown).is_some());
+        assert!(state.handle_event(&press, 1.0, &mut unknown).is_some());
+        assert!(!unknown);
+    }
+
+    #[test]
+    fn unmap_mouse_with_overrides() {
+        let mut state = InputState::new();
+        let code = unmap_mouse(MouseButton::X1).map(|button| match button {
+            WinitMouseButton::Other(code) => code,
+            button => panic!("unexpected button {:?}", button),
+        }).unwrap();
+        state.mouse_overrides.insert(code, MouseButton::Button8);
+        assert_eq!(state.map_mouse(WinitMouseButton::Other(code)), MouseButton::Button8);
+        assert_eq!(state.unmap_mouse(MouseButton::Button8), Some(WinitMouseButton::Other(code)));
+        // The code maps to `X1` by default, which is now unreachable.
+        assert_eq!(state.unmap_mouse(MouseButton::X1), None);
+        state.mouse_overrides.remove(&code);
+        assert_eq!(state.unmap_mouse(MouseButton::X1), Some(WinitMouseButton::Other(code)));
+    }
+
+    #[test]
+    fn last_scroll_keeps_unit() {
+        let mut state = InputState::new();
+        state.scroll_policy = ScrollPolicy { lines_per_pixel: 0.25, ..ScrollPolicy::default() };
+        let mut unknown = false;
+
+        let pixels = scroll_event(MouseScrollDelta::PixelDelta(LogicalPosition::new(40.0, 20.0)));
+        assert_eq!(state.handle_event(&pixels, 1.0, &mut unknown),
+                   Some(Input::Move(Motion::MouseScroll([10.0, 5.0]))));
+        assert_eq!(state.last_scroll, Some(ScrollDelta::Pixels([40.0, 20.0])));
+
+        let lines = scroll_event(MouseScrollDelta::LineDelta(0.0, 3.0));
+        assert_eq!(state.handle_event(&lines, 1.0, &mut unknown),
+                   Some(Input::Move(Motion::MouseScroll([0.0, 3.0]))));
+        assert_eq!(state.last_scroll, Some(ScrollDelta::Lines([0.0, 3.0])));
+    }
+}
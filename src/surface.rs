@@ -0,0 +1,59 @@
+//! Vulkan surface creation, enabled by the `use-vulkano` feature.
+//!
+//! The surface is created from the native handles of the winit window,
+//! such that the window type is the one used by this crate.
+
+use std::sync::Arc;
+
+use vulkano::instance::Instance;
+use vulkano::swapchain::{Surface, SurfaceCreationError};
+use winit::window::Window as OriginalWinitWindow;
+
+/// Creates a Vulkan surface that owns the window.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+          target_os = "netbsd", target_os = "openbsd"))]
+pub fn create_surface(
+    instance: Arc<Instance>,
+    window: OriginalWinitWindow
+) -> Result<Arc<Surface<OriginalWinitWindow>>, SurfaceCreationError> {
+    use std::os::raw::c_void;
+    use winit::platform::unix::WindowExtUnix;
+
+    if let (Some(display), Some(surface)) = (window.wayland_display(), window.wayland_surface()) {
+        return unsafe {
+            Surface::from_wayland(instance, display as *const c_void, surface as *const c_void, window)
+        };
+    }
+    match (window.xlib_display(), window.xlib_window()) {
+        (Some(display), Some(xwin)) => unsafe {
+            Surface::from_xlib(instance, display as *const c_void, xwin, window)
+        },
+        _ => Err(SurfaceCreationError::MissingExtension { name: "VK_KHR_xlib_surface" }),
+    }
+}
+
+/// Creates a Vulkan surface that owns the window.
+#[cfg(target_os = "windows")]
+pub fn create_surface(
+    instance: Arc<Instance>,
+    window: OriginalWinitWindow
+) -> Result<Arc<Surface<OriginalWinitWindow>>, SurfaceCreationError> {
+    use std::os::raw::c_void;
+    use winit::platform::windows::WindowExtWindows;
+
+    let hinstance = window.hinstance() as *const c_void;
+    let hwnd = window.hwnd() as *const c_void;
+    unsafe { Surface::from_hwnd(instance, hinstance, hwnd, window) }
+}
+
+/// Creates a Vulkan surface that owns the window.
+///
+/// Only Unix and Windows are supported.
+#[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd",
+              target_os = "netbsd", target_os = "openbsd", target_os = "windows")))]
+pub fn create_surface(
+    _instance: Arc<Instance>,
+    _window: OriginalWinitWindow
+) -> Result<Arc<Surface<OriginalWinitWindow>>, SurfaceCreationError> {
+    Err(SurfaceCreationError::MissingExtension { name: "VK_MVK_macos_surface" })
+}
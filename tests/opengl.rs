@@ -0,0 +1,39 @@
+//! Creates OpenGL windows with settings that need a visual other than the default one.
+//!
+//! Requires a display, e.g. run with `xvfb-run` and `LIBGL_ALWAYS_SOFTWARE=1`
+//! to test on Mesa's software rasterizer.
+//! Runs without the test harness, since the event loop must be created on the main thread.
+
+extern crate glow;
+extern crate rg_winit_window;
+extern crate window;
+
+use glow::HasContext;
+use window::{OpenGLWindow, Window, WindowSettings};
+
+use rg_winit_window::WinitWindow;
+
+fn main() {
+    let settings = WindowSettings::new("opengl", (64, 64));
+    let cases = vec![
+        ("default", settings.clone()),
+        ("transparent", settings.clone().transparent(true)),
+        ("samples", settings.clone().samples(4)),
+        ("transparent and samples", settings.clone().transparent(true).samples(4)),
+    ];
+    for (name, settings) in cases {
+        let mut window = WinitWindow::try_new(&settings)
+            .unwrap_or_else(|err| panic!("{}: {}", name, err));
+        assert!(window.is_current(), "{}: context is not current", name);
+        {
+            let gl = window.gl().expect("an OpenGL context");
+            unsafe {
+                gl.clear_color(0.2, 0.3, 0.4, 1.0);
+                gl.clear(glow::COLOR_BUFFER_BIT);
+                assert_eq!(gl.get_error(), glow::NO_ERROR, "{}", name);
+                println!("{}: {}", name, gl.get_parameter_string(glow::RENDERER));
+            }
+        }
+        window.swap_buffers();
+    }
+}